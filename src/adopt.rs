@@ -0,0 +1,141 @@
+use crate::args::push::push_git;
+use crate::lib;
+use git2::{Config, ErrorCode, Repository};
+use lib::{
+    expand_home, is_offline, save_template, set_folders, DmanError, Template, DEFAULT_REMOTE,
+};
+use std::{fs, path::Path};
+
+/// Written into .gitignore of adopted folder, if it doesn't have one
+const DEFAULT_GITIGNORE: &str = "\
+# Editors and OS
+*~
+*.swp
+*.swo
+.DS_Store
+Thumbs.db
+
+# Logs, caches and runtime state
+*.log
+*.tmp
+*.pid
+*.sock
+.cache/
+__pycache__/
+node_modules/
+";
+
+/// Bring existing folder under management of dman
+///
+/// Folder is turned into Git repository with .gitignore and initial commit,
+/// then it's registered as template
+/// Git repository without commits is adopted too
+/// If `git_path` is set, it's added as origin and initial commit is pushed there
+///
+/// # Errors
+///
+/// * If path isn't a folder or it's already a Git repository with commits
+/// * If Git author (user.name and user.email) isn't set
+/// * If template with the same name exists
+/// * If Git repository can't be created, committed or pushed, template is registered
+///   before pushing, so push can be repeated with `dman push`
+pub fn adopt(
+    path: String,
+    name: Option<String>,
+    git_path: Option<String>,
+) -> Result<(), DmanError> {
+    let path = fs::canonicalize(expand_home(&path))
+        .map_err(|_| DmanError::NotFound(format!("Folder {path:?}")))?;
+
+    if !path.is_dir() {
+        return Err(DmanError::InvalidInput(format!("{path:?} isn't a folder")));
+    }
+
+    // Repository without commits is adopted, committed one can be registered as it is
+    let existing = Repository::open(&path).ok();
+    if let Some(repo) = &existing {
+        if !repo
+            .head()
+            .is_err_and(|e| e.code() == ErrorCode::UnbornBranch)
+        {
+            return Err(DmanError::InvalidInput(format!(
+                "{path:?} is already a Git repository, register it with `dman new`"
+            )));
+        }
+    }
+
+    // Initial commit needs author, so nothing is created without it
+    let config = match &existing {
+        Some(repo) => repo.config()?,
+        None => Config::open_default()?,
+    };
+    if config.get_string("user.name").is_err() || config.get_string("user.email").is_err() {
+        return Err(DmanError::InvalidInput(
+            "Set author of commits with `git config --global user.name <name>` and \
+             `git config --global user.email <email>`"
+                .to_string(),
+        ));
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => path
+            .file_name()
+            .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+            .ok_or_else(|| DmanError::InvalidInput(format!("{path:?} has no name")))?,
+    };
+
+    let template_file = Path::new(&set_folders()?).join(format!("{name}.toml"));
+    if template_file.exists() {
+        return Err(DmanError::AlreadyExists(format!("Template {name:?}")));
+    }
+
+    let repo = match existing {
+        Some(repo) => repo,
+        None => {
+            let repo = Repository::init(&path)?;
+            println!("Initialized Git repository in {path:?}");
+            repo
+        }
+    };
+
+    let gitignore = path.join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(&gitignore, DEFAULT_GITIGNORE)?;
+        println!("Created {gitignore:?}");
+    }
+
+    if let Some(git_path) = &git_path {
+        if repo.find_remote(DEFAULT_REMOTE).is_ok() {
+            repo.remote_set_url(DEFAULT_REMOTE, git_path)?;
+        } else {
+            repo.remote(DEFAULT_REMOTE, git_path)?;
+        }
+        println!("Added remote {DEFAULT_REMOTE}: {git_path}");
+    }
+
+    push_git::commit(&path, Some("Initial commit".to_string()))?;
+
+    let template = Template {
+        name: name.clone(),
+        path: path.to_string_lossy().to_string(),
+        git_path: git_path.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+    save_template(&template)?;
+    println!("Template {name:?} created");
+
+    match git_path {
+        // Template is registered, so failed push can be repeated with `dman push`
+        Some(_) if is_offline() => {
+            println!("Offline, push initial commit with `dman push {name}` when online")
+        }
+        Some(_) => push_git::push_current_branch(&path, DEFAULT_REMOTE)?,
+        None => println!(
+            "Template has no remote, add it with `git remote add {DEFAULT_REMOTE} <url>` \
+             and set git_path in {template_file:?}"
+        ),
+    }
+
+    Ok(())
+}
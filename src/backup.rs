@@ -0,0 +1,405 @@
+use crate::args::list::style_table;
+use crate::lib;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use lib::{
+    config::{BackupConfig, Config},
+    get_data_folder, DmanError,
+};
+use mytools::warn;
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tabled::{Table, Tabled};
+use walkdir::WalkDir;
+
+/// Description of backup, stored next to its archive as `<id>.toml`
+#[derive(Debug, Deserialize, Serialize)]
+struct Manifest {
+    /// What dman was about to do
+    reason: String,
+    /// Seconds since Unix epoch
+    created: u64,
+    /// Absolute paths of backed up files
+    files: Vec<String>,
+}
+
+/// Row of backup table
+#[derive(Tabled)]
+struct BackupRow {
+    id: String,
+    created: String,
+    reason: String,
+    files: usize,
+}
+
+/// Get ~/.local/share/dotfile-manager/backups/
+fn backups_folder() -> Result<PathBuf, DmanError> {
+    let folder = get_data_folder()?.join("backups");
+    fs::create_dir_all(&folder)?;
+
+    Ok(folder)
+}
+
+fn archive_path(folder: &Path, id: &str) -> PathBuf {
+    folder.join(format!("{id}.tar.gz"))
+}
+
+fn manifest_path(folder: &Path, id: &str) -> PathBuf {
+    folder.join(format!("{id}.toml"))
+}
+
+/// Name of file inside archive, absolute path without root, so archive can be browsed by tar
+fn entry_name(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Get files inside paths, folders are walked without .git
+///
+/// Symlinks point into repositories, so only regular files are backed up
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, DmanError> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue;
+        };
+
+        if metadata.is_file() {
+            files.push(path.clone());
+        } else if metadata.is_dir() {
+            let walker = WalkDir::new(path)
+                .into_iter()
+                .filter_entry(|entry| entry.file_name() != ".git");
+
+            for entry in walker {
+                let entry = entry.map_err(io::Error::from)?;
+                if entry.file_type().is_file() {
+                    files.push(entry.into_path());
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn seconds_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Write files into new archive without removing old backups
+fn create_snapshot(
+    label: &str,
+    reason: &str,
+    paths: &[PathBuf],
+) -> Result<Option<String>, DmanError> {
+    let files = collect_files(paths)?;
+
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let now = SystemTime::now();
+    // 20231024T154500Z, sorts the same way as time
+    let timestamp = humantime::format_rfc3339_seconds(now)
+        .to_string()
+        .replace(['-', ':'], "");
+    let label: String = label
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+
+    let folder = backups_folder()?;
+    let mut id = format!("{timestamp}-{label}");
+    let mut n = 1;
+    // More snapshots can be made in one second
+    while archive_path(&folder, &id).exists() {
+        n += 1;
+        id = format!("{timestamp}-{label}-{n}");
+    }
+
+    let archive = File::create(archive_path(&folder, &id))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(archive, Compression::default()));
+    for file in &files {
+        builder.append_path_with_name(file, entry_name(file))?;
+    }
+    builder.into_inner()?.finish()?;
+
+    let manifest = Manifest {
+        reason: reason.to_string(),
+        created: seconds_since_epoch(now),
+        files: files
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect(),
+    };
+    let contents = toml::to_string(&manifest)
+        .map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize backup: {e}")))?;
+    fs::write(manifest_path(&folder, &id), contents)?;
+
+    Ok(Some(id))
+}
+
+/// Back up files into timestamped archive in ~/.local/share/dotfile-manager/backups/,
+/// before dman overwrites or removes them
+///
+/// `label` is part of the backup id, e.g. name of the template
+/// Old backups are removed afterwards, as set in config.toml
+///
+/// Returns id of backup, None if there was nothing to back up
+///
+/// # Errors
+///
+/// * If archive can't be written, caller shouldn't overwrite files then
+pub fn snapshot(label: &str, reason: &str, paths: &[PathBuf]) -> Result<Option<String>, DmanError> {
+    let id = create_snapshot(label, reason, paths)?;
+
+    // Backup was made, so failing cleanup shouldn't stop anything
+    if let Err(e) = prune() {
+        warn!("Couldn't remove old backups: {e}");
+    }
+
+    Ok(id)
+}
+
+/// Read manifests of every backup, newest first
+fn read_manifests(folder: &Path) -> Result<Vec<(String, Manifest)>, DmanError> {
+    let mut manifests = Vec::new();
+
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("toml") {
+            continue;
+        }
+
+        let Some(id) = path.file_stem() else {
+            continue;
+        };
+        let manifest: Manifest =
+            toml::from_str(&fs::read_to_string(&path)?).map_err(|source| DmanError::TomlParse {
+                file: path.to_string_lossy().to_string(),
+                source,
+            })?;
+
+        manifests.push((id.to_string_lossy().to_string(), manifest));
+    }
+
+    manifests.sort_by(|(a_id, a), (b_id, b)| b.created.cmp(&a.created).then(b_id.cmp(a_id)));
+
+    Ok(manifests)
+}
+
+fn remove_backup(folder: &Path, id: &str) -> Result<(), DmanError> {
+    let archive = archive_path(folder, id);
+    if archive.exists() {
+        fs::remove_file(archive)?;
+    }
+    fs::remove_file(manifest_path(folder, id))?;
+
+    Ok(())
+}
+
+/// Check if backup is over limits of retention policy
+///
+/// Newest backup is always kept
+fn is_expired(config: &BackupConfig, position: usize, created: u64, now: u64) -> bool {
+    if position == 0 {
+        return false;
+    }
+
+    let max_age = config.max_age_days * 24 * 60 * 60;
+
+    position >= config.keep || (config.max_age_days > 0 && now.saturating_sub(created) > max_age)
+}
+
+/// Remove backups over limits set in config.toml
+fn prune() -> Result<(), DmanError> {
+    let config = Config::load()?.backup;
+    let folder = backups_folder()?;
+    let now = seconds_since_epoch(SystemTime::now());
+
+    for (position, (id, manifest)) in read_manifests(&folder)?.into_iter().enumerate() {
+        if is_expired(&config, position, manifest.created, now) {
+            remove_backup(&folder, &id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print table of backups, newest first
+///
+/// # Errors
+///
+/// * If backups folder or manifest can't be read
+pub fn list_backups() -> Result<(), DmanError> {
+    let manifests = read_manifests(&backups_folder()?)?;
+
+    if manifests.is_empty() {
+        println!("{}", "No backups found".red());
+        return Ok(());
+    }
+
+    let rows: Vec<BackupRow> = manifests
+        .into_iter()
+        .map(|(id, manifest)| BackupRow {
+            id,
+            created: humantime::format_rfc3339_seconds(
+                UNIX_EPOCH + Duration::from_secs(manifest.created),
+            )
+            .to_string(),
+            reason: manifest.reason,
+            files: manifest.files.len(),
+        })
+        .collect();
+
+    let mut table = Table::new(&rows);
+    style_table(&mut table);
+    println!("{table}");
+    println!("Restore backup with `dman backup restore <id>`");
+
+    Ok(())
+}
+
+/// Put files of backup back to their places
+///
+/// Files, that are there now, are backed up first, so restoring can be undone
+///
+/// # Errors
+///
+/// * If backup doesn't exist
+/// * If archive can't be read or files can't be written
+pub fn restore_backup(id: &str) -> Result<(), DmanError> {
+    let folder = backups_folder()?;
+    let manifest_path = manifest_path(&folder, id);
+
+    if !manifest_path.exists() {
+        return Err(DmanError::NotFound(format!("Backup {id}")));
+    }
+
+    let manifest: Manifest =
+        toml::from_str(&fs::read_to_string(&manifest_path)?).map_err(|source| {
+            DmanError::TomlParse {
+                file: manifest_path.to_string_lossy().to_string(),
+                source,
+            }
+        })?;
+    let targets: Vec<PathBuf> = manifest.files.iter().map(PathBuf::from).collect();
+
+    // Backup being restored could be removed by retention, so old backups are removed at the end
+    if let Some(current) = create_snapshot("restore", &format!("before restoring {id}"), &targets)?
+    {
+        println!("Current files backed up as {current}");
+    }
+
+    let targets: HashMap<PathBuf, &PathBuf> = targets
+        .iter()
+        .map(|target| (entry_name(target), target))
+        .collect();
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path(&folder, id))?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_path_buf();
+        let Some(target) = targets.get(&name) else {
+            continue;
+        };
+
+        // Writing into deployed symlink would change file in repository
+        if target
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            fs::remove_file(target)?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        entry.unpack(target)?;
+        println!("Restored {target:?}");
+    }
+
+    if let Err(e) = prune() {
+        warn!("Couldn't remove old backups: {e}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_expired, read_manifests, BackupConfig};
+    use std::fs;
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 1_000 * DAY;
+
+    fn config(keep: usize, max_age_days: u64) -> BackupConfig {
+        BackupConfig { keep, max_age_days }
+    }
+
+    #[test]
+    fn backups_over_count_expire() {
+        let config = config(3, 0);
+
+        assert!(!is_expired(&config, 1, NOW, NOW));
+        assert!(!is_expired(&config, 2, NOW, NOW));
+        assert!(is_expired(&config, 3, NOW, NOW));
+        assert!(is_expired(&config, 10, NOW, NOW));
+    }
+
+    #[test]
+    fn old_backups_expire() {
+        let config = config(50, 30);
+
+        assert!(!is_expired(&config, 1, NOW - 30 * DAY, NOW));
+        assert!(is_expired(&config, 1, NOW - 30 * DAY - 1, NOW));
+        // Backup from the future, e.g. after clock change, isn't expired
+        assert!(!is_expired(&config, 1, NOW + DAY, NOW));
+    }
+
+    #[test]
+    fn zero_age_keeps_old_backups() {
+        assert!(!is_expired(&config(50, 0), 1, 0, NOW));
+    }
+
+    #[test]
+    fn newest_backup_is_kept() {
+        assert!(!is_expired(&config(0, 1), 0, 0, NOW));
+    }
+
+    #[test]
+    fn manifests_are_sorted_newest_first() {
+        let folder = std::env::temp_dir().join(format!("dman-backups-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        for (id, created) in [("a", 2), ("b", 3), ("c", 1), ("d", 3)] {
+            fs::write(
+                folder.join(format!("{id}.toml")),
+                format!("reason = \"test\"\ncreated = {created}\nfiles = []\n"),
+            )
+            .unwrap();
+        }
+        fs::write(folder.join("a.tar.gz"), "").unwrap();
+
+        let ids: Vec<String> = read_manifests(&folder)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(ids, ["d", "b", "a", "c"]);
+    }
+}
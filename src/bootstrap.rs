@@ -0,0 +1,207 @@
+use crate::args::{
+    create::{clone_git, clone_template},
+    deploy::{link, OnExisting},
+    import::{import_templates, read_exported, ImportOptions, Source},
+    pull::pull_git::{self, PullOptions},
+};
+use crate::lib;
+use git2::Repository;
+use lib::{
+    expand_home, get_branches, get_data_folder, get_templates_to_vec, is_offline, machine::Machine,
+    save_template, set_folders, DeployMode, DmanError, Template, DEFAULT_REMOTE,
+};
+use mytools::warn;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Export files looked for in manifest repository, when `--file` isn't set
+const MANIFEST_FILES: [&str; 5] = [
+    "dman.toml",
+    "dman.json",
+    "dman.yaml",
+    "dman.yml",
+    "templates.toml",
+];
+
+/// Clone manifest repository into ~/.local/share/dotfile-manager/manifest,
+/// or pull it, if it was cloned by earlier bootstrap
+fn fetch_manifest(git_url: &str) -> Result<PathBuf, DmanError> {
+    let path = get_data_folder()?.join("manifest");
+
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+        println!("Cloning manifest from {git_url}");
+        if let Err(e) = clone_git::run(git_url, &path) {
+            // Empty folder would stop every following bootstrap
+            fs::remove_dir_all(&path)?;
+            return Err(e);
+        }
+        return Ok(path);
+    }
+
+    let repo = Repository::open(&path)?;
+    let origin = repo.find_remote(DEFAULT_REMOTE)?;
+    if origin.url() != Some(git_url) {
+        return Err(DmanError::InvalidInput(format!(
+            "{path:?} has manifest of {}, remove it to bootstrap from {git_url}",
+            origin.url().unwrap_or_default()
+        )));
+    }
+
+    let path_string = path.to_string_lossy().to_string();
+    let options = PullOptions {
+        on_dirty: None,
+        strategy: Default::default(),
+        quiet: false,
+    };
+    pull_git::run(
+        path_string.clone(),
+        DEFAULT_REMOTE,
+        get_branches(path_string)?,
+        options,
+    )?;
+
+    Ok(path)
+}
+
+/// Find export file in manifest repository
+fn manifest_file(manifest: &Path, file: Option<String>) -> Result<PathBuf, DmanError> {
+    if let Some(file) = file {
+        let path = manifest.join(&file);
+        if !path.is_file() {
+            return Err(DmanError::NotFound(format!(
+                "{file} in manifest repository"
+            )));
+        }
+        return Ok(path);
+    }
+
+    MANIFEST_FILES
+        .iter()
+        .map(|name| manifest.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            DmanError::NotFound(format!(
+                "{} in manifest repository, set file with --file",
+                MANIFEST_FILES.join(", ")
+            ))
+        })
+}
+
+/// Check if template path has to be cloned
+fn is_missing(path: &Path) -> Result<bool, DmanError> {
+    Ok(!path.exists() || (path.is_dir() && path.read_dir()?.next().is_none()))
+}
+
+/// Provision this machine from manifest repository with export file of dman
+///
+/// Manifest is cloned, templates for this machine, that aren't on disk yet, are cloned,
+/// every template is imported and files of deployed templates are linked
+/// Templates of other machines are only registered, `dman pull` clones them later
+///
+/// `on_existing` decides what happens with files already at targets, they're backed up
+/// if it's None
+///
+/// # Errors
+///
+/// * If dman is offline
+/// * If manifest can't be cloned, or it doesn't contain export file
+/// * If any template couldn't be cloned or deployed, the rest is bootstrapped anyway
+pub fn bootstrap(
+    git_url: String,
+    file: Option<String>,
+    on_existing: Option<OnExisting>,
+) -> Result<(), DmanError> {
+    if is_offline() {
+        return Err(DmanError::Offline("Bootstrap".to_string()));
+    }
+
+    let manifest = fetch_manifest(&git_url)?;
+    let manifest_file = manifest_file(&manifest, file)?;
+
+    let machine = Machine::current()?;
+    let template_folder = set_folders()?;
+    let existing: Vec<String> = get_templates_to_vec()?
+        .into_iter()
+        .map(|template| template.name)
+        .collect();
+    let mut deployed: Vec<Template> = Vec::new();
+    let mut failed = 0;
+
+    for mut template in read_exported(&manifest_file)? {
+        // Template file, that can't be read, isn't overwritten either
+        if existing.contains(&template.name)
+            || Path::new(&template_folder)
+                .join(format!("{}.toml", template.name))
+                .exists()
+        {
+            continue;
+        }
+
+        let path = expand_home(&template.path);
+        template.path = path.to_string_lossy().to_string();
+
+        let for_this_machine = template.enabled && template.applies_to(&machine);
+        // Repository, that is already there, is checked by import
+        if is_missing(&path)? {
+            if !for_this_machine {
+                // Registered, so import doesn't offer to clone it
+                save_template(&template)?;
+                println!(
+                    "Template {:?} registered without cloning, it's not for this machine",
+                    template.name
+                );
+                continue;
+            }
+
+            println!("Cloning template {:?}", template.name);
+            fs::create_dir_all(&path)?;
+            if let Err(e) = clone_template(&template, &path) {
+                warn!("Couldn't clone {}: {e}", template.name);
+                save_template(&template)?;
+                println!(
+                    "Template {0:?} registered, clone it with `dman pull {0}`",
+                    template.name
+                );
+                failed += 1;
+                continue;
+            }
+        }
+
+        if for_this_machine && template.deploy != DeployMode::InPlace {
+            deployed.push(template);
+        }
+    }
+
+    import_templates(
+        manifest_file.to_string_lossy().to_string(),
+        Some(Source::Dman),
+        ImportOptions::default(),
+    )?;
+
+    for template in deployed {
+        println!("Deploying template {:?}", template.name);
+
+        if let Err(e) = link(
+            Some(template.name.clone()),
+            None,
+            None,
+            Some(on_existing.unwrap_or(OnExisting::Backup)),
+        ) {
+            warn!("{e}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!(
+            "{failed} template(s) couldn't be bootstrapped"
+        )));
+    }
+
+    println!("Machine bootstrapped from {git_url}");
+
+    Ok(())
+}
@@ -1,28 +1,118 @@
+#[path = "../adopt.rs"]
+mod adopt;
+#[path = "../backup.rs"]
+mod backup;
+#[path = "../bootstrap.rs"]
+mod bootstrap;
+#[path = "../conflicts.rs"]
+mod conflicts;
 #[path = "../create.rs"]
 mod create;
+#[path = "../deploy.rs"]
+mod deploy;
+#[path = "../doctor.rs"]
+mod doctor;
+#[path = "../drift.rs"]
+mod drift;
+#[path = "../edit.rs"]
+mod edit;
 #[path = "../export.rs"]
 mod export;
+#[path = "../hooks.rs"]
+mod hooks;
 #[path = "../import.rs"]
 mod import;
 #[path = "list.rs"]
 mod list;
+#[path = "../migrate.rs"]
+mod migrate;
 #[path = "../pull.rs"]
 mod pull;
+#[path = "../push.rs"]
+mod push;
 #[path = "../remove.rs"]
 mod remove;
+#[path = "../secret.rs"]
+mod secret;
+#[path = "status.rs"]
+mod status;
 #[path = "../updater.rs"]
 mod updater;
 
+use crate::lib::{config::Config, set_offline, DmanError, PullStrategy};
+use adopt::adopt;
+use backup::{list_backups, restore_backup};
+use bootstrap::bootstrap;
 use clap::{Arg, ArgAction, Command};
+use conflicts::{resolve_conflicts, Resolution};
 use create::create_template;
-use export::export_templates;
-use import::import_templates;
+use deploy::{link, unlink, OnExisting};
+use doctor::doctor;
+use drift::drift;
+use edit::{edit_template, Changes};
+use export::{export_templates, Filter, Format};
+use hooks::{run_hook_by_hand, Hook};
+use import::{import_templates, ImportOptions, OnConflict, Source};
 use list::list_templates;
-use mytools::pretty_panic;
-use pull::{pull, pull_all};
+use migrate::migrate_templates;
+use pull::{pull, pull_all, pull_git::DirtyAction, DEFAULT_JOBS};
+use push::push;
 use remove::remove_template;
+use secret::{add_secrets, edit_secret, rekey_secrets};
+use status::status_templates;
 use updater::check_updates;
 
+/// Arguments used to select a template: template name, -n, -p, -g
+fn template_selection_args() -> [Arg; 4] {
+    [
+        Arg::new("template")
+            .required(false)
+            .value_hint(clap::ValueHint::Unknown),
+        Arg::new("name")
+            .short('n')
+            .long("name")
+            .action(ArgAction::Append)
+            .value_hint(clap::ValueHint::Unknown)
+            .help("Name of the template"),
+        Arg::new("path")
+            .short('p')
+            .long("path")
+            .action(ArgAction::Append)
+            .value_hint(clap::ValueHint::DirPath)
+            .help("Path to the template"),
+        Arg::new("git-path")
+            .short('g')
+            .long("git-path")
+            .action(ArgAction::Append)
+            .value_hint(clap::ValueHint::Url)
+            .help("Git path to the template"),
+    ]
+}
+
+/// Argument deciding what happens with uncommitted changes when pulling
+fn on_dirty_arg() -> Arg {
+    Arg::new("on-dirty")
+        .long("on-dirty")
+        .value_parser(["abort", "stash", "force"])
+        .help("What to do with uncommitted changes, asks for every template if not set")
+}
+
+/// Flag including templates, that don't apply to this machine
+fn all_hosts_arg() -> Arg {
+    Arg::new("all-hosts")
+        .long("all-hosts")
+        .action(ArgAction::SetTrue)
+        .help("Include templates for other machines")
+}
+
+/// Argument overriding pull strategy of templates
+fn strategy_arg() -> Arg {
+    Arg::new("strategy")
+        .long("strategy")
+        .value_parser(["ff-only", "merge", "rebase"])
+        .help("How to pull diverged branches, overrides pull_strategy of template")
+}
+
 /// Get arguments from Clap
 fn arguments() -> Command {
     Command::new("dman")
@@ -30,6 +120,13 @@ fn arguments() -> Command {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .author("Adamekka")
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .help("Don't contact remotes, checks of remotes are deferred and pulls skipped")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .subcommand(
             Command::new("new")
                 .about("Create new template")
@@ -62,23 +159,224 @@ fn arguments() -> Command {
                         .help("Git path to the template"),
                 ),
         )
-        .subcommand(Command::new("list").about("List all templates"))
+        .subcommand(
+            Command::new("adopt")
+                .about("Turn existing folder into Git repository and register it as template")
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .value_hint(clap::ValueHint::DirPath)
+                        .help("Folder to adopt, e.g. ~/.config/alacritty"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_hint(clap::ValueHint::Unknown)
+                        .help("Name of the template, name of the folder if not set"),
+                )
+                .arg(
+                    Arg::new("git-path")
+                        .short('g')
+                        .long("git-path")
+                        .value_hint(clap::ValueHint::Url)
+                        .help("Remote added as origin, initial commit is pushed there"),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Change fields of template, empty value removes optional field")
+                .arg(
+                    Arg::new("template")
+                        .required(true)
+                        .value_hint(clap::ValueHint::Unknown)
+                        .help("Name of the template"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .value_hint(clap::ValueHint::Unknown)
+                        .help("New name, template file is renamed"),
+                )
+                .arg(
+                    Arg::new("path")
+                        .short('p')
+                        .long("path")
+                        .value_hint(clap::ValueHint::DirPath)
+                        .help("New path, repository is moved or cloned there, if it's not there"),
+                )
+                .arg(
+                    Arg::new("git-path")
+                        .short('g')
+                        .long("git-path")
+                        .value_hint(clap::ValueHint::Url)
+                        .help("New git path, remote of repository is changed too"),
+                )
+                .arg(
+                    Arg::new("move")
+                        .long("move")
+                        .action(ArgAction::SetTrue)
+                        .help("Move repository to new path without asking"),
+                )
+                .arg(
+                    Arg::new("branch")
+                        .long("branch")
+                        .help("Branch to pull, every local branch is pulled if it's empty"),
+                )
+                .arg(
+                    Arg::new("remote")
+                        .long("remote")
+                        .help("Name of the remote, origin if it's empty"),
+                )
+                .arg(Arg::new("description").long("description"))
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .action(ArgAction::Append)
+                        .help("Replaces tags, can be used multiple times"),
+                )
+                .arg(
+                    Arg::new("enabled")
+                        .long("enabled")
+                        .value_parser(clap::value_parser!(bool))
+                        .help("Disabled templates are skipped by pull-all"),
+                )
+                .arg(
+                    Arg::new("pull-strategy")
+                        .long("pull-strategy")
+                        .help("ff-only, merge or rebase"),
+                )
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .action(ArgAction::Append)
+                        .help("Replaces hostname patterns, can be used multiple times"),
+                )
+                .arg(
+                    Arg::new("machine-tag")
+                        .long("machine-tag")
+                        .action(ArgAction::Append)
+                        .help("Replaces machine tags, can be used multiple times"),
+                )
+                .arg(
+                    Arg::new("deploy")
+                        .long("deploy")
+                        .value_parser(["in-place", "symlink", "copy"])
+                        .help("How files of template are deployed"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .action(ArgAction::Append)
+                        .help("Replaces deployed files, source:target, can be used multiple times"),
+                )
+                .arg(
+                    Arg::new("hook")
+                        .long("hook")
+                        .action(ArgAction::Append)
+                        .help("Sets hook, e.g. post_pull=make, can be used multiple times"),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List all templates")
+                .arg(all_hosts_arg()),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show Git status of all templates: branch, changes, ahead/behind")
+                .arg(all_hosts_arg()),
+        )
+        .subcommand(
+            Command::new("bootstrap")
+                .about("Set up this machine from repository with exported templates")
+                .arg(
+                    Arg::new("git-url")
+                        .required(true)
+                        .value_hint(clap::ValueHint::Url),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Export file in repository, dman.toml, dman.json, dman.yaml or templates.toml if not set"),
+                )
+                .arg(
+                    Arg::new("on-existing")
+                        .long("on-existing")
+                        .value_parser(["backup", "skip", "overwrite"])
+                        .help("What to do with files already at targets, they're backed up if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check setup of dman and every template")
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .action(ArgAction::SetTrue)
+                        .help("Repair what can be repaired, e.g. remote URL or missing repository"),
+                ),
+        )
         .subcommand(
             Command::new("import")
-                .about("Import template(s) from toml file")
+                .about("Import template(s) from toml file or other dotfile manager")
                 .arg(
                     Arg::new("file")
                         .required(true)
-                        .value_hint(clap::ValueHint::FilePath),
+                        .value_hint(clap::ValueHint::AnyPath)
+                        .help("Exported toml, json or yaml file, Stow folder, dotbot config, yadm repository or chezmoi source folder"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_parser(["dman", "stow", "dotbot", "yadm", "chezmoi"])
+                        .help("Where templates are imported from, detected from file if not set"),
+                )
+                .arg(
+                    Arg::new("on-conflict")
+                        .long("on-conflict")
+                        .value_parser(["skip", "overwrite", "rename", "ask"])
+                        .default_value("skip")
+                        .help("What to do with templates, that already exist and differ"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Only show what would be added, changed or skipped"),
                 ),
         )
         .subcommand(
             Command::new("export")
-                .about("Export template(s) to toml file")
+                .about("Export template(s) to file or stdout")
                 .arg(
                     Arg::new("file")
                         .required(false)
-                        .value_hint(clap::ValueHint::FilePath),
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("File to write, templates are printed to stdout if it's not set"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["toml", "json", "yaml"])
+                        .help("Format of export, taken from extension of file if not set"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .action(ArgAction::Append)
+                        .value_hint(clap::ValueHint::Unknown)
+                        .help("Export only templates with name matching glob, e.g. 'nvim*'"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .short('t')
+                        .long("tag")
+                        .action(ArgAction::Append)
+                        .help("Export only templates with tag"),
                 ),
         )
         .subcommand(
@@ -141,14 +439,34 @@ fn arguments() -> Command {
                         .action(ArgAction::Append)
                         .value_hint(clap::ValueHint::Url)
                         .help("Git path to the template"),
-                ),
+                )
+                .arg(on_dirty_arg())
+                .arg(strategy_arg()),
         )
         .subcommand(
-            Command::new("pull-all").about("Clone all templates and pull changes from remote"),
+            Command::new("pull-all")
+                .about("Clone all templates and pull changes from remote")
+                .arg(on_dirty_arg())
+                .arg(strategy_arg())
+                .arg(all_hosts_arg())
+                .arg(
+                    Arg::new("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .value_parser(clap::value_parser!(u16).range(1..))
+                        .help(format!(
+                            "How many templates are pulled at once [default: {DEFAULT_JOBS}]"
+                        )),
+                ),
         )
         .subcommand(
             Command::new("push")
-                .about("Push changes to Git repo")
+                .about("Commit local changes and push them to Git repo")
+                .arg(
+                    Arg::new("template")
+                        .required(false)
+                        .value_hint(clap::ValueHint::Unknown),
+                )
                 .arg(
                     Arg::new("name")
                         .short('n')
@@ -172,8 +490,147 @@ fn arguments() -> Command {
                         .action(ArgAction::Append)
                         .value_hint(clap::ValueHint::Url)
                         .help("Git path to the template"),
+                )
+                .arg(
+                    Arg::new("message")
+                        .short('m')
+                        .long("message")
+                        .value_hint(clap::ValueHint::Other)
+                        .help("Commit message, generated from changed files if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("conflicts")
+                .about("List merge conflicts of template and resolve them")
+                .args(template_selection_args())
+                .arg(
+                    Arg::new("resolve")
+                        .long("resolve")
+                        .value_parser(["ours", "theirs", "manual"])
+                        .help("How to resolve conflicts, asks for every file if not set"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .action(ArgAction::Append)
+                        .value_hint(clap::ValueHint::FilePath)
+                        .help("Resolve only this file, can be used multiple times"),
+                ),
+        )
+        .subcommand(
+            Command::new("link")
+                .about("Deploy files of template to their targets, as symlinks or copies")
+                .visible_alias("deploy")
+                .args(template_selection_args())
+                .arg(
+                    Arg::new("on-existing")
+                        .long("on-existing")
+                        .value_parser(["backup", "skip", "overwrite"])
+                        .help("What to do with files already at targets, asks for every file if not set"),
+                ),
+        )
+        .subcommand(
+            Command::new("unlink")
+                .about("Remove deployed files of template and restore backed up files")
+                .visible_alias("undeploy")
+                .args(template_selection_args()),
+        )
+        .subcommand(
+            Command::new("drift")
+                .about("Show deployed copies, that changed since they were deployed")
+                .args(template_selection_args())
+                .arg(
+                    Arg::new("pull-back")
+                        .long("pull-back")
+                        .action(ArgAction::SetTrue)
+                        .help("Copy edited copies back into repository"),
+                ),
+        )
+        .subcommand(
+            Command::new("backup")
+                .about("Browse and restore backups made before dman overwrote files")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List backups, newest first"))
+                .subcommand(
+                    Command::new("restore")
+                        .about("Put files of backup back to their places")
+                        .arg(
+                            Arg::new("id")
+                                .required(true)
+                                .value_hint(clap::ValueHint::Unknown)
+                                .help("Id of the backup from `dman backup list`"),
+                        ),
                 ),
         )
+        .subcommand(
+            Command::new("hooks")
+                .about("Run hooks of template")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("run")
+                        .about("Run hook of template by hand")
+                        .args(template_selection_args())
+                        .arg(
+                            Arg::new("hook")
+                                .long("hook")
+                                .value_parser([
+                                    "pre_clone",
+                                    "post_clone",
+                                    "pre_pull",
+                                    "post_pull",
+                                    "pre_push",
+                                    "post_push",
+                                ])
+                                .default_value("post_pull")
+                                .help("Hook to run"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("secret")
+                .about("Manage files, that are committed only encrypted")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Encrypt files of template and exclude them from Git")
+                        .arg(
+                            Arg::new("template")
+                                .required(true)
+                                .value_hint(clap::ValueHint::Unknown)
+                                .help("Name of the template"),
+                        )
+                        .arg(
+                            Arg::new("file")
+                                .required(true)
+                                .action(ArgAction::Append)
+                                .value_hint(clap::ValueHint::FilePath)
+                                .help("File inside repository of template"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("edit")
+                        .about("Edit secret of template in $EDITOR and encrypt it again")
+                        .arg(
+                            Arg::new("template")
+                                .required(true)
+                                .value_hint(clap::ValueHint::Unknown)
+                                .help("Name of the template"),
+                        )
+                        .arg(
+                            Arg::new("file")
+                                .required(true)
+                                .value_hint(clap::ValueHint::FilePath)
+                                .help("Secret inside repository of template"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rekey")
+                        .about("Create new key and encrypt secrets of every template with it"),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate").about("Rewrite template files to the current template version"),
+        )
         .subcommand(Command::new("update").about("Update dman to the latest version"))
 }
 
@@ -252,66 +709,249 @@ fn generate_shell_completions() {
     };
 }
 
+/// Check if doctor is run, it diagnoses setup of dman, so setup isn't done before it
+pub fn runs_doctor() -> bool {
+    arguments()
+        .try_get_matches()
+        .is_ok_and(|matches| matches.subcommand_name() == Some("doctor"))
+}
+
 /// Match arguments: new, pull, push, ...
 /// Then pass them to according function with their parameters
-pub fn match_args() {
+pub fn match_args() -> Result<(), DmanError> {
     let args = arguments().get_matches();
 
     #[cfg(target_family = "unix")]
     generate_shell_completions();
 
+    // Broken config.toml shouldn't stop commands, that don't need it
+    set_offline(
+        args.get_flag("offline")
+            || Config::load().map_or_else(
+                |e| {
+                    mytools::warn!("Offline setting of config is ignored, {e}");
+                    false
+                },
+                |config| config.remote.offline,
+            ),
+    );
+
     match args.subcommand() {
         Some(("new", _set_matches)) => {
             let (name, path, git_path) = match_subcmd_flags("new");
-            create_template(name, path, git_path);
+            create_template(name, path, git_path)?;
         }
 
-        Some(("list", _set_matches)) => {
-            list_templates();
+        Some(("adopt", set_matches)) => {
+            let path = set_matches.get_one::<String>("path").unwrap().clone();
+            let name = set_matches.get_one::<String>("name").cloned();
+            let git_path = set_matches.get_one::<String>("git-path").cloned();
+            adopt(path, name, git_path)?;
         }
 
-        Some(("import", _set_matches)) => {
-            let file_path = get_toml_file_from_import();
-            import_templates(file_path);
+        Some(("edit", set_matches)) => {
+            let name = set_matches.get_one::<String>("template").unwrap().clone();
+            let values = |arg: &str| {
+                set_matches
+                    .get_many::<String>(arg)
+                    .map(|values| values.cloned().collect())
+            };
+            let changes = Changes {
+                name: set_matches.get_one::<String>("name").cloned(),
+                path: set_matches.get_one::<String>("path").cloned(),
+                git_path: set_matches.get_one::<String>("git-path").cloned(),
+                branch: set_matches.get_one::<String>("branch").cloned(),
+                remote: set_matches.get_one::<String>("remote").cloned(),
+                description: set_matches.get_one::<String>("description").cloned(),
+                tags: values("tag"),
+                enabled: set_matches.get_one::<bool>("enabled").copied(),
+                pull_strategy: set_matches.get_one::<String>("pull-strategy").cloned(),
+                hosts: values("host"),
+                machine_tags: values("machine-tag"),
+                deploy: set_matches.get_one::<String>("deploy").cloned(),
+                files: values("file"),
+                hooks: values("hook").unwrap_or_default(),
+            };
+            edit_template(name, changes, set_matches.get_flag("move"))?;
         }
 
-        Some(("export", _arg_matches)) => {
-            // Get export_file from arguments
-            if let Some(arg_matches) = args.subcommand_matches("export") {
-                let export_file = arg_matches.get_one::<String>("file").unwrap().to_string();
+        Some(("list", set_matches)) => {
+            list_templates(set_matches.get_flag("all-hosts"))?;
+        }
 
-                export_templates(export_file);
-            }
+        Some(("status", set_matches)) => {
+            status_templates(set_matches.get_flag("all-hosts"))?;
+        }
+
+        Some(("bootstrap", set_matches)) => {
+            let git_url = set_matches.get_one::<String>("git-url").unwrap().clone();
+            let file = set_matches.get_one::<String>("file").cloned();
+            let on_existing = match set_matches.get_one::<String>("on-existing") {
+                Some(action) => Some(action.parse::<OnExisting>()?),
+                None => None,
+            };
+            bootstrap(git_url, file, on_existing)?;
+        }
+
+        Some(("doctor", set_matches)) => {
+            doctor(set_matches.get_flag("fix"))?;
+        }
+
+        Some(("import", set_matches)) => {
+            let file_path = get_toml_file_from_import()?;
+            let from = set_matches
+                .get_one::<String>("from")
+                .map(|from| from.parse::<Source>())
+                .transpose()?;
+            let options = ImportOptions {
+                on_conflict: set_matches
+                    .get_one::<String>("on-conflict")
+                    .unwrap()
+                    .parse::<OnConflict>()?,
+                dry_run: set_matches.get_flag("dry-run"),
+            };
+            import_templates(file_path, from, options)?;
+        }
+
+        Some(("export", set_matches)) => {
+            let export_file = set_matches.get_one::<String>("file").cloned();
+            let format = set_matches
+                .get_one::<String>("format")
+                .map(|format| format.parse::<Format>())
+                .transpose()?;
+            let filter = Filter {
+                names: set_matches
+                    .get_many::<String>("name")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect(),
+                tags: set_matches
+                    .get_many::<String>("tag")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect(),
+            };
+            export_templates(export_file, format, &filter)?;
         }
 
         Some(("remove", _set_matches)) => {
-            check_if_enough_flags("remove");
+            check_if_enough_flags("remove")?;
             let (name, path, git_path) = match_subcmd_flags("remove");
-            remove_template(name, path, git_path);
+            remove_template(name, path, git_path)?;
         }
 
-        Some(("pull", _set_matches)) => {
-            check_if_enough_flags("pull");
+        Some(("pull", set_matches)) => {
+            check_if_enough_flags("pull")?;
             let (name, path, git_path) = match_subcmd_flags("pull");
-            pull(name, path, git_path);
+            let on_dirty = get_on_dirty(set_matches)?;
+            let strategy = get_strategy(set_matches)?;
+            pull(name, path, git_path, on_dirty, strategy)?;
+        }
+
+        Some(("pull-all", set_matches)) => {
+            let on_dirty = get_on_dirty(set_matches)?;
+            let strategy = get_strategy(set_matches)?;
+            let jobs = set_matches
+                .get_one::<u16>("jobs")
+                .copied()
+                .unwrap_or(DEFAULT_JOBS);
+            let all_hosts = set_matches.get_flag("all-hosts");
+            pull_all(on_dirty, strategy, usize::from(jobs), all_hosts)?;
+        }
+
+        Some(("push", set_matches)) => {
+            check_if_enough_flags("push")?;
+            let (name, path, git_path) = match_subcmd_flags("push");
+            let message = set_matches.get_one::<String>("message").cloned();
+            push(name, path, git_path, message)?;
         }
 
-        Some(("pull-all", _set_matches)) => {
-            pull_all();
+        Some(("conflicts", set_matches)) => {
+            check_if_enough_flags("conflicts")?;
+            let (name, path, git_path) = match_subcmd_flags("conflicts");
+            let resolution = match set_matches.get_one::<String>("resolve") {
+                Some(resolution) => Some(resolution.parse::<Resolution>()?),
+                None => None,
+            };
+            let files = set_matches
+                .get_many::<String>("file")
+                .map(|files| files.cloned().collect())
+                .unwrap_or_default();
+            resolve_conflicts(name, path, git_path, resolution, files)?;
         }
 
-        Some(("push", _set_matches)) => {
-            check_if_enough_flags("push");
-            let (_name, _path, _git_path) = match_subcmd_flags("push");
-            todo!("push");
+        Some(("link", set_matches)) => {
+            check_if_enough_flags("link")?;
+            let (name, path, git_path) = match_subcmd_flags("link");
+            let on_existing = match set_matches.get_one::<String>("on-existing") {
+                Some(action) => Some(action.parse::<OnExisting>()?),
+                None => None,
+            };
+            link(name, path, git_path, on_existing)?;
+        }
+
+        Some(("unlink", _set_matches)) => {
+            check_if_enough_flags("unlink")?;
+            let (name, path, git_path) = match_subcmd_flags("unlink");
+            unlink(name, path, git_path)?;
+        }
+
+        Some(("drift", set_matches)) => {
+            let (name, path, git_path) = match_subcmd_flags("drift");
+            drift(name, path, git_path, set_matches.get_flag("pull-back"))?;
+        }
+
+        Some(("backup", set_matches)) => match set_matches.subcommand() {
+            Some(("list", _list_matches)) => list_backups()?,
+            Some(("restore", restore_matches)) => {
+                restore_backup(restore_matches.get_one::<String>("id").unwrap())?;
+            }
+            _ => unreachable!(),
+        },
+
+        Some(("hooks", set_matches)) => match set_matches.subcommand() {
+            Some(("run", run_matches)) => {
+                let (name, path, git_path) = template_selection(run_matches);
+                let hook = run_matches
+                    .get_one::<String>("hook")
+                    .unwrap()
+                    .parse::<Hook>()?;
+                run_hook_by_hand(name, path, git_path, hook)?;
+            }
+            _ => unreachable!(),
+        },
+
+        Some(("secret", set_matches)) => match set_matches.subcommand() {
+            Some(("add", add_matches)) => {
+                let name = add_matches.get_one::<String>("template").unwrap().clone();
+                let files = add_matches
+                    .get_many::<String>("file")
+                    .unwrap()
+                    .cloned()
+                    .collect();
+                add_secrets(name, files)?;
+            }
+            Some(("edit", edit_matches)) => {
+                let name = edit_matches.get_one::<String>("template").unwrap().clone();
+                let file = edit_matches.get_one::<String>("file").unwrap().clone();
+                edit_secret(name, file)?;
+            }
+            Some(("rekey", _rekey_matches)) => rekey_secrets()?,
+            _ => unreachable!(),
+        },
+
+        Some(("migrate", _set_matches)) => {
+            migrate_templates()?;
         }
 
         Some(("update", _set_matches)) => {
-            check_updates();
+            check_updates()?;
         }
 
         _ => unreachable!(),
     }
+
+    Ok(())
 }
 
 /// Match subcommand flags: -n, -p, -g; --name, --path, --git-path
@@ -324,27 +964,39 @@ fn match_subcmd_flags(
 ) {
     let args = arguments().get_matches();
 
+    if let Some(arg_match) = args.subcommand_matches(cmd) {
+        template_selection(arg_match)
+    } else {
+        unreachable!("Clap somehow screwed up");
+    }
+}
+
+/// Get template selection from matches of subcommand with template_selection_args,
+/// nested subcommands like `hooks run` use it directly
+fn template_selection(
+    arg_match: &clap::ArgMatches,
+) -> (
+    Option<std::string::String>,
+    Option<std::string::String>,
+    Option<std::string::String>,
+) {
     let mut name: Option<String> = None;
     let mut path: Option<String> = None;
     let mut git_path: Option<String> = None;
 
-    if let Some(arg_match) = args.subcommand_matches(cmd) {
-        // If name flag is present use it, otherwise use subcommand argument as Template name
-        if arg_match.get_one::<String>("name").is_some() {
-            name = arg_match.get_one::<String>("name").cloned();
-        } else if arg_match.get_one::<String>("template").is_some() {
-            name = arg_match.get_one::<String>("template").cloned();
-        }
+    // If name flag is present use it, otherwise use subcommand argument as Template name
+    if arg_match.get_one::<String>("name").is_some() {
+        name = arg_match.get_one::<String>("name").cloned();
+    } else if arg_match.get_one::<String>("template").is_some() {
+        name = arg_match.get_one::<String>("template").cloned();
+    }
 
-        if arg_match.get_one::<String>("path").is_some() {
-            path = arg_match.get_one::<String>("path").cloned();
-        }
+    if arg_match.get_one::<String>("path").is_some() {
+        path = arg_match.get_one::<String>("path").cloned();
+    }
 
-        if arg_match.get_one::<String>("git-path").is_some() {
-            git_path = arg_match.get_one::<String>("git-path").cloned();
-        }
-    } else {
-        unreachable!("Clap somehow screwed up");
+    if arg_match.get_one::<String>("git-path").is_some() {
+        git_path = arg_match.get_one::<String>("git-path").cloned();
     }
 
     #[cfg(debug_assertions)]
@@ -356,8 +1008,8 @@ fn match_subcmd_flags(
 }
 
 /// Check if at least 1 flag or name of Template is present
-/// If not, panic
-fn check_if_enough_flags(cmd: &str) {
+/// If not, return error
+fn check_if_enough_flags(cmd: &str) -> Result<(), DmanError> {
     let args = arguments().get_matches();
 
     if let Some(arg_match) = args.subcommand_matches(cmd) {
@@ -366,15 +1018,37 @@ fn check_if_enough_flags(cmd: &str) {
             && arg_match.get_one::<String>("git-path").is_none()
             && arg_match.get_one::<String>("template").is_none()
         {
-            pretty_panic!("At least 1 flag or name of Template is required");
+            return Err(DmanError::InvalidInput(
+                "At least 1 flag or name of Template is required".to_string(),
+            ));
         }
     } else {
         unreachable!("Clap somehow screwed up");
     }
+
+    Ok(())
+}
+
+/// Get action for uncommitted changes from --on-dirty flag
+fn get_on_dirty(arg_matches: &clap::ArgMatches) -> Result<Option<DirtyAction>, DmanError> {
+    match arg_matches.get_one::<String>("on-dirty") {
+        Some(action) => Ok(Some(action.parse().map_err(|e: git2::Error| {
+            DmanError::InvalidInput(e.message().to_string())
+        })?)),
+        None => Ok(None),
+    }
+}
+
+/// Get pull strategy from --strategy flag
+fn get_strategy(arg_matches: &clap::ArgMatches) -> Result<Option<PullStrategy>, DmanError> {
+    match arg_matches.get_one::<String>("strategy") {
+        Some(strategy) => Ok(Some(strategy.parse()?)),
+        None => Ok(None),
+    }
 }
 
 /// Get toml file when using import subcommand
-fn get_toml_file_from_import() -> String {
+fn get_toml_file_from_import() -> Result<String, DmanError> {
     let args = arguments().get_matches();
 
     if let Some(arg_match) = args.subcommand_matches("import") {
@@ -382,9 +1056,9 @@ fn get_toml_file_from_import() -> String {
             let file_path = arg_match.get_one::<String>("file").unwrap().to_string();
             println!("{file_path:?}",);
 
-            file_path
+            Ok(file_path)
         } else {
-            pretty_panic!("No file specified");
+            Err(DmanError::InvalidInput("No file specified".to_string()))
         }
     } else {
         unreachable!("Clap somehow screwed up");
@@ -1,6 +1,8 @@
 use crate::args::remove::remove_template;
 use crate::lib;
-use lib::{get_existing_templates, process_template_to_struct, Template};
+use lib::{
+    get_existing_templates, machine::Machine, process_template_to_struct, DmanError, Template,
+};
 use mytools::{question_yes_no, warn};
 use owo_colors::OwoColorize;
 use std::path::Path;
@@ -17,20 +19,67 @@ impl Template {
             name,
             path,
             git_path,
+            ..Default::default()
+        }
+    }
+}
+
+/// Apply dman table style and fit table into terminal
+pub fn style_table(table: &mut Table) {
+    let table_style = RawStyle::from(Style::rounded()).colored();
+
+    let color = Color::try_from(" ".red().to_string()).unwrap();
+
+    table
+        .with(table_style)
+        .with(Highlight::colored(Segment::all(), BorderColored::default()))
+        .with(color);
+
+    // Get terminal size, there is none when output is piped
+    if let Some(term_size) = termsize::get() {
+        // Get table width
+        let table_width = table.total_width();
+
+        // If table is bigger than terminal, truncate words in it
+        if table_width > term_size.cols as usize {
+            let columns = table.count_columns().max(1);
+            table.with(
+                Modify::new(Rows::new(1..))
+                    .with(Width::truncate(term_size.cols as usize / columns).suffix("..")),
+            );
+            warn!("Table is too big for terminal, so it's resized");
         }
     }
 }
 
 /// Print listed templates
-pub fn list_templates() {
+///
+/// Templates for other machines are listed only if `all_hosts` is true
+pub fn list_templates(all_hosts: bool) -> Result<(), DmanError> {
     println!("Listing templates...");
 
-    let templates = get_existing_templates();
+    let machine = Machine::current()?;
+    let templates = get_existing_templates()?;
     let mut data: Vec<Template> = Vec::new();
     let mut non_existing_templates: Vec<Template> = Vec::new();
+    let mut other_hosts = 0;
 
     for template_file in templates {
-        let template = process_template_to_struct(&template_file);
+        // Show other templates, even if one of them is broken
+        let template = match process_template_to_struct(&template_file) {
+            Ok(template) => template,
+            Err(e) => {
+                warn!("Skipping template: {e}");
+                continue;
+            }
+        };
+
+        let applies = template.applies_to(&machine);
+        if !applies && !all_hosts {
+            other_hosts += 1;
+            continue;
+        }
+
         data.push(Template::new(
             template.name.clone(),
             template.path.clone(),
@@ -38,8 +87,9 @@ pub fn list_templates() {
         ));
 
         // Check if template is in filesystem
+        // Templates of other machines aren't expected to be here
         let template_path = Path::new(&template.path);
-        if !template_path.exists() {
+        if applies && !template_path.exists() {
             non_existing_templates.push(Template::new(
                 template.name,
                 template.path,
@@ -60,33 +110,18 @@ pub fn list_templates() {
     // Sort templates by name alphabetically
     data.sort_by(|a, b| a.name.cmp(&b.name));
 
-    let table_style = RawStyle::from(Style::rounded()).colored();
-
-    let color = Color::try_from(" ".red().to_string()).unwrap();
-
-    let mut table = Table::from_iter(&data);
-    table
-        .with(table_style)
-        .with(Highlight::colored(Segment::all(), BorderColored::default()))
-        .with(color);
+    let mut table = Table::new(&data);
+    style_table(&mut table);
 
-    // Get terminal size
-    let term_size = termsize::get().expect("Failed to get terminal size");
-    // Get table width
-    let table_width = table.total_width();
+    // Print table
+    println!("{table}");
 
-    // If table is bigger than terminal, truncate words in it
-    if table_width > term_size.cols as usize {
-        table.with(
-            Modify::new(Rows::new(1..))
-                .with(Width::truncate(term_size.cols as usize / 3).suffix("..")),
+    if other_hosts > 0 {
+        println!(
+            "{other_hosts} template(s) for other machines hidden, use --all-hosts to show them"
         );
-        warn!("Table is too big for terminal, so it's resized");
     }
 
-    // Print table
-    println!("{table}");
-
     if !non_existing_templates.is_empty() {
         warn!("Some templates are not in filesystem: {non_existing_templates:?}");
         question_yes_no!("Do you want to remove templates, that aren't in filesystem?");
@@ -95,7 +130,9 @@ pub fn list_templates() {
                 Some(template.name),
                 Some(template.path),
                 Some(template.git_path),
-            )
+            )?;
         }
     }
+
+    Ok(())
 }
@@ -2,10 +2,20 @@ mod args;
 #[path = "../lib.rs"]
 mod lib;
 
-use args::match_args;
+use args::{match_args, runs_doctor};
 use lib::set_folders;
+use owo_colors::OwoColorize;
 
 fn main() {
-    set_folders();
-    match_args();
+    // Doctor checks the setup itself, so broken setup can't stop it
+    let result = if runs_doctor() {
+        match_args()
+    } else {
+        set_folders().and_then(|_| match_args())
+    };
+
+    if let Err(e) = result {
+        eprintln!("{} {e}", "Error:".red());
+        std::process::exit(e.exit_code());
+    }
 }
@@ -0,0 +1,188 @@
+use crate::args::list::style_table;
+use crate::lib;
+use git2::{BranchType, Repository, Status, StatusOptions};
+use lib::{
+    expand_home, get_existing_templates, machine::Machine, process_template_to_struct, DmanError,
+    Template,
+};
+use mytools::warn;
+use owo_colors::OwoColorize;
+use tabled::{Table, Tabled};
+
+/// Row of status table
+#[derive(Tabled)]
+struct TemplateStatus {
+    name: String,
+    branch: String,
+    modified: String,
+    untracked: String,
+    ahead: String,
+    behind: String,
+    state: String,
+}
+
+impl TemplateStatus {
+    /// Row for template, whose repository couldn't be inspected
+    fn unavailable(name: String, state: String) -> Self {
+        Self {
+            name,
+            branch: "-".to_string(),
+            modified: "-".to_string(),
+            untracked: "-".to_string(),
+            ahead: "-".to_string(),
+            behind: "-".to_string(),
+            state,
+        }
+    }
+}
+
+/// Get name of checked out branch, or description of HEAD if it's not on a branch
+fn get_branch_name(repo: &Repository) -> String {
+    match repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().unwrap_or("?").to_string(),
+        Ok(head) => match head.target() {
+            Some(oid) => format!("detached at {:.7}", oid.to_string()),
+            None => "detached".to_string(),
+        },
+        Err(_) => "no commits".to_string(),
+    }
+}
+
+/// Count modified and untracked files in working tree
+fn count_changes(repo: &Repository) -> Result<(usize, usize), git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+
+    let mut modified = 0;
+    let mut untracked = 0;
+
+    for entry in repo.statuses(Some(&mut options))?.iter() {
+        let status = entry.status();
+
+        if status.contains(Status::WT_NEW) {
+            untracked += 1;
+        } else if !status.is_ignored() && status != Status::CURRENT {
+            modified += 1;
+        }
+    }
+
+    Ok((modified, untracked))
+}
+
+/// Count commits ahead and behind upstream of checked out branch
+///
+/// Returns None if branch has no upstream
+fn count_ahead_behind(repo: &Repository) -> Result<Option<(usize, usize)>, git2::Error> {
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        _ => return Ok(None),
+    };
+
+    let Some(branch_name) = head.shorthand() else {
+        return Ok(None);
+    };
+    let branch = repo.find_branch(branch_name, BranchType::Local)?;
+
+    let Ok(upstream) = branch.upstream() else {
+        return Ok(None);
+    };
+
+    match (branch.get().target(), upstream.get().target()) {
+        (Some(local), Some(upstream)) => Ok(Some(repo.graph_ahead_behind(local, upstream)?)),
+        _ => Ok(None),
+    }
+}
+
+/// Inspect repository of template
+fn get_template_status(template: &Template) -> TemplateStatus {
+    let path = expand_home(&template.path);
+
+    if !path.exists() {
+        return TemplateStatus::unavailable(
+            template.name.clone(),
+            "path missing".red().to_string(),
+        );
+    }
+
+    let repo = match Repository::open(&path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            return TemplateStatus::unavailable(
+                template.name.clone(),
+                "not a git repo".red().to_string(),
+            )
+        }
+    };
+
+    let (modified, untracked) = match count_changes(&repo) {
+        Ok(changes) => changes,
+        Err(e) => {
+            return TemplateStatus::unavailable(
+                template.name.clone(),
+                e.message().red().to_string(),
+            )
+        }
+    };
+
+    let ahead_behind = count_ahead_behind(&repo).unwrap_or(None);
+    let (ahead, behind) = match ahead_behind {
+        Some((ahead, behind)) => (ahead.to_string(), behind.to_string()),
+        None => ("-".to_string(), "-".to_string()),
+    };
+
+    let state = if modified > 0 || untracked > 0 {
+        "dirty".yellow().to_string()
+    } else if ahead_behind.is_some_and(|(ahead, _)| ahead > 0) {
+        "unpushed".yellow().to_string()
+    } else if ahead_behind.is_some_and(|(_, behind)| behind > 0) {
+        "behind".yellow().to_string()
+    } else {
+        "clean".green().to_string()
+    };
+
+    TemplateStatus {
+        name: template.name.clone(),
+        branch: get_branch_name(&repo),
+        modified: modified.to_string(),
+        untracked: untracked.to_string(),
+        ahead,
+        behind,
+        state,
+    }
+}
+
+/// Print Git status of every template
+///
+/// Ahead and behind are counted against last fetched state of upstream,
+/// run pull to get up to date numbers
+/// Templates for other machines are checked only if `all_hosts` is true
+pub fn status_templates(all_hosts: bool) -> Result<(), DmanError> {
+    println!("Checking templates...");
+
+    let machine = Machine::current()?;
+    let mut templates: Vec<Template> = Vec::new();
+    for template_file in get_existing_templates()? {
+        match process_template_to_struct(&template_file) {
+            Ok(template) if all_hosts || template.applies_to(&machine) => templates.push(template),
+            Ok(_) => {}
+            Err(e) => warn!("Skipping template: {e}"),
+        }
+    }
+
+    // Sort templates by name alphabetically
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let data: Vec<TemplateStatus> = templates.iter().map(get_template_status).collect();
+
+    if data.is_empty() {
+        println!("{}", "No templates found".red());
+        return Ok(());
+    }
+
+    let mut table = Table::new(&data);
+    style_table(&mut table);
+
+    println!("{table}");
+
+    Ok(())
+}
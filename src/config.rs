@@ -0,0 +1,110 @@
+use super::DmanError;
+use mytools::env::get_home_folder;
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+/// Settings of dman, written by user
+///
+/// ```toml
+/// [backup]
+/// keep = 20
+/// max_age_days = 30
+///
+/// [remote]
+/// timeout_secs = 10
+/// cache_secs = 300
+/// offline = false
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+}
+
+/// Retention of backups dman makes before it overwrites files
+#[derive(Clone, Debug, Deserialize)]
+pub struct BackupConfig {
+    /// How many newest backups are kept
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+    /// Older backups are removed, 0 keeps them regardless of age
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_keep() -> usize {
+    50
+}
+
+fn default_max_age_days() -> u64 {
+    90
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            keep: default_keep(),
+            max_age_days: default_max_age_days(),
+        }
+    }
+}
+
+/// Checking if remotes are reachable
+#[derive(Clone, Debug, Deserialize)]
+pub struct RemoteConfig {
+    /// How long to wait for remote to respond, 0 waits forever
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// How long reachable remote isn't checked again, 0 disables caching
+    #[serde(default = "default_cache_secs")]
+    pub cache_secs: u64,
+    /// Don't contact remotes, like --offline flag
+    #[serde(default)]
+    pub offline: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+fn default_cache_secs() -> u64 {
+    300
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_timeout_secs(),
+            cache_secs: default_cache_secs(),
+            offline: false,
+        }
+    }
+}
+
+/// Path to ~/.config/dotfile-manager/config.toml
+pub fn config_file_path() -> PathBuf {
+    PathBuf::from(get_home_folder()).join(".config/dotfile-manager/config.toml")
+}
+
+impl Config {
+    /// Read config.toml, missing file means default settings
+    ///
+    /// # Errors
+    ///
+    /// * If config.toml can't be read or parsed
+    pub fn load() -> Result<Self, DmanError> {
+        let path = config_file_path();
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|source| DmanError::TomlParse {
+            file: path.to_string_lossy().to_string(),
+            source,
+        })
+    }
+}
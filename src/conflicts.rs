@@ -0,0 +1,245 @@
+use crate::args::{backup::snapshot, pull::pull_git::conflicted_files};
+use crate::lib;
+use git2::{Index, IndexConflict, Repository, RepositoryState};
+use lib::{expand_home, match_user_input_with_existing_templates, DmanError};
+use mytools::warn;
+use question::{Answer, Question};
+use std::{fmt, fs, path::Path, slice, str::FromStr};
+
+/// How to resolve conflicted file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep local version
+    Ours,
+    /// Take version from remote
+    Theirs,
+    /// File was edited by user, mark it as resolved
+    Manual,
+}
+
+impl FromStr for Resolution {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ours" | "o" => Ok(Resolution::Ours),
+            "theirs" | "t" => Ok(Resolution::Theirs),
+            "manual" | "m" => Ok(Resolution::Manual),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown resolution {s:?}, use ours, theirs or manual"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolution::Ours => write!(f, "ours"),
+            Resolution::Theirs => write!(f, "theirs"),
+            Resolution::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// Ask user how to resolve file, None means skip it
+fn ask_resolution(file: &str) -> Option<Resolution> {
+    let answer = Question::new(&format!(
+        "Resolve {file} using ours, theirs, manual or skip?"
+    ))
+    .acceptable(vec!["ours", "theirs", "manual", "skip", "o", "t", "m", "s"])
+    .until_acceptable()
+    .default(Answer::RESPONSE("skip".to_string()))
+    .show_defaults()
+    .ask();
+
+    match answer {
+        Some(Answer::RESPONSE(resolution)) => resolution.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Check if file still contains conflict markers written by merge
+fn has_conflict_markers(contents: &str) -> bool {
+    contents
+        .lines()
+        .any(|line| line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> "))
+}
+
+fn find_conflict(index: &Index, file: &str) -> Result<IndexConflict, DmanError> {
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let path = [&conflict.our, &conflict.their, &conflict.ancestor]
+            .into_iter()
+            .flatten()
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .next();
+
+        if path.as_deref() == Some(file) {
+            return Ok(conflict);
+        }
+    }
+
+    Err(DmanError::NotFound(format!("Conflict in {file}")))
+}
+
+/// Resolve one conflicted file and stage the result
+///
+/// Returns false if file couldn't be resolved
+fn resolve_file(
+    repo: &Repository,
+    index: &mut Index,
+    file: &str,
+    resolution: Resolution,
+) -> Result<bool, DmanError> {
+    let Some(workdir) = repo.workdir() else {
+        return Err(DmanError::InvalidInput(
+            "Repository has no working tree".to_string(),
+        ));
+    };
+    let full_path = workdir.join(file);
+    let conflict = find_conflict(index, file)?;
+
+    let entry = match resolution {
+        Resolution::Ours => conflict.our,
+        Resolution::Theirs => conflict.their,
+        Resolution::Manual => {
+            if let Ok(contents) = fs::read_to_string(&full_path) {
+                if has_conflict_markers(&contents) {
+                    warn!("{file} still contains conflict markers, skipping");
+                    return Ok(false);
+                }
+            }
+
+            if full_path.exists() {
+                index.add_path(Path::new(file))?;
+            } else {
+                index.remove_path(Path::new(file))?;
+            }
+
+            println!("Resolved {file} manually");
+            return Ok(true);
+        }
+    };
+
+    // Working file can have conflicts partly resolved by hand
+    let label = workdir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    snapshot(
+        &label,
+        &format!("conflicts --resolve {resolution}"),
+        slice::from_ref(&full_path),
+    )?;
+
+    match entry {
+        Some(entry) => {
+            let blob = repo.find_blob(entry.id)?;
+            fs::write(&full_path, blob.content())?;
+            index.add_path(Path::new(file))?;
+        }
+        // File was deleted on chosen side
+        None => {
+            if full_path.exists() {
+                fs::remove_file(&full_path)?;
+            }
+            index.remove_path(Path::new(file))?;
+        }
+    }
+
+    println!("Resolved {file} using {resolution}");
+
+    Ok(true)
+}
+
+/// Create merge commit, if merge is in progress and there are no conflicts left
+fn finish_merge(repo: &mut Repository) -> Result<(), DmanError> {
+    if repo.state() != RepositoryState::Merge {
+        return Ok(());
+    }
+
+    let mut merge_heads = Vec::new();
+    repo.mergehead_foreach(|oid| {
+        merge_heads.push(*oid);
+        true
+    })?;
+
+    let mut index = repo.index()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let head = repo.head()?.peel_to_commit()?;
+
+    let mut parents = vec![head.clone()];
+    for oid in &merge_heads {
+        parents.push(repo.find_commit(*oid)?);
+    }
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+    let merged: Vec<String> = merge_heads.iter().map(|oid| oid.to_string()).collect();
+    let msg = format!("Merge: {} into {}", merged.join(", "), head.id());
+    let sig = repo.signature()?;
+
+    let merge_commit = repo.commit(Some("HEAD"), &sig, &sig, &msg, &tree, &parents)?;
+    repo.cleanup_state()?;
+
+    println!("All conflicts resolved, created merge commit {merge_commit}");
+
+    Ok(())
+}
+
+/// List merge conflicts of template and resolve them
+///
+/// If `resolution` is None, user is asked for every file
+/// If `files` is empty, every conflicted file is resolved
+/// When no conflicts are left, merge commit is created
+pub fn resolve_conflicts(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    resolution: Option<Resolution>,
+    files: Vec<String>,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+    let mut repo = Repository::open(expand_home(&template.path))?;
+    let mut index = repo.index()?;
+
+    let conflicted = conflicted_files(&index)?;
+
+    if conflicted.is_empty() {
+        println!("No conflicts in {}", template.name);
+        return finish_merge(&mut repo);
+    }
+
+    println!("Conflicted files in {}:", template.name);
+    for file in &conflicted {
+        println!("  {file}");
+    }
+
+    for file in &conflicted {
+        if !files.is_empty() && !files.contains(file) {
+            continue;
+        }
+
+        let resolution = match resolution {
+            Some(resolution) => resolution,
+            None => match ask_resolution(file) {
+                Some(resolution) => resolution,
+                None => continue,
+            },
+        };
+
+        resolve_file(&repo, &mut index, file, resolution)?;
+    }
+
+    index.write()?;
+
+    let left = conflicted_files(&index)?;
+    if !left.is_empty() {
+        return Err(DmanError::MergeConflict {
+            template: template.name,
+            files: left,
+        });
+    }
+
+    finish_merge(&mut repo)
+}
@@ -1,23 +1,16 @@
 #[path = "./libgit2-rs/clone_git.rs"]
 pub mod clone_git;
 
+use crate::args::{
+    backup::snapshot,
+    hooks::{head_commit, run_hook, Commits, Hook},
+};
 use crate::lib;
-use lib::{check_if_remote_exists, set_folders};
-use mytools::{env::get_home_folder, pretty_panic, question_yes_no};
-use serde::Serialize;
-use std::{fs, path::Path};
-
-#[derive(Clone, Serialize)]
-struct Template {
-    name: Option<String>,
-    path: Option<String>,
-    git_path: Option<String>,
-}
-
-#[derive(Serialize)]
-struct Toml {
-    template: Template,
-}
+use lib::{
+    check_if_remote_exists, expand_home, set_folders, write_template_file, DmanError, Template,
+};
+use mytools::question_yes_no;
+use std::path::Path;
 
 /// Construct a struct with template parameters
 ///
@@ -27,10 +20,10 @@ struct Toml {
 /// * `path` - Path to the template
 /// * `git_path` - Path to the git repository
 ///
-/// # Panics
+/// # Errors
 ///
 /// * If template already exists
-/// * If path does not exist
+/// * If path is not a git repository
 /// * If git path does not exist
 ///
 /// # Examples
@@ -38,139 +31,152 @@ struct Toml {
 /// ```
 /// use dotfile_manager::create_template;
 ///
-/// create_template(Some("test".to_string()), Some("/home/user/test".to_string()), Some("https://github.com/user/repository".to_string()));
+/// create_template(Some("test".to_string()), Some("/home/user/test".to_string()), Some("https://github.com/user/repository".to_string()))?;
 /// ```
-pub fn create_template(name: Option<String>, path: Option<String>, git_path: Option<String>) {
-    let template_folder = set_folders();
+pub fn create_template(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+) -> Result<(), DmanError> {
+    let template_folder = set_folders()?;
+
+    let (Some(name), Some(path), Some(git_path)) = (name, path, git_path) else {
+        return Err(DmanError::InvalidInput(
+            "Template needs name, path and git path".to_string(),
+        ));
+    };
 
     let template = Template {
         name,
         path,
         git_path,
+        ..Default::default()
     };
 
-    write_template_to_fs(template, template_folder);
+    write_template_to_fs(template, template_folder)
 }
 
-/// Write template to filesystem
-fn write_template_to_fs(template: Template, template_folder: String) {
-    // This is needed because i want toml to have table name
-    // [template]
-    // name = "..."
-    // path = "..."
-    // git_path = "..."
-    let mut template = Toml { template };
-
-    // Create file contents
-    let mut toml = toml::to_string(&template).unwrap();
-
-    // Replace ~ with home path
-    // this is needed because ~ is not expanded by the std::path::Path
-    // and the toml crate does not expand it either
-    let home = get_home_folder();
-    toml = toml.replace('~', home.as_str());
-
-    // Create file path
-    let template_path_string =
-        template_folder.clone() + "/" + template.template.name.as_ref().unwrap() + ".toml";
-    let template_path = Path::new(&template_path_string);
+/// Clone repository of template with its pre_clone and post_clone hooks
+///
+/// Anything already in path is backed up first
+pub fn clone_template(template: &Template, path: &Path) -> Result<(), DmanError> {
+    snapshot(&template.name, "clone", &[path.to_path_buf()])?;
+    run_hook(template, Hook::PreClone, &Commits::default(), false)?;
 
-    // Check if template already exists
-    if template_path.exists() {
-        pretty_panic!("Same path template already exists");
-    }
+    clone_git::run(&template.git_path, path)?;
+    println!("Cloned template from Git repository");
 
-    // Check if path defined in template exists
-    let mut tmp = template.template.path.as_mut().unwrap().clone();
-    // Replace ~ with home path
-    // this is needed because ~ is not expanded by the std::path::Path
-    // and the toml crate does not expand it either
-    tmp = tmp.replace('~', home.as_str());
+    let commits = Commits {
+        old: None,
+        new: head_commit(&template.path),
+    };
+    run_hook(template, Hook::PostClone, &commits, false)
+}
 
-    let path_in_template = Path::new(&tmp);
-    if !path_in_template.exists() {
-        println!("Path {path_in_template:?} does not exist");
+/// Clone repository of template, if its path doesn't exist or is empty, user is asked first
+///
+/// # Errors
+///
+/// * If path is a file
+/// * If user refuses to clone or repository can't be cloned
+pub fn clone_if_missing(template: &Template, path: &Path) -> Result<(), DmanError> {
+    if !path.exists() {
+        println!("Path {path:?} does not exist");
         question_yes_no!("Do you want to clone this template from Git repository?");
 
-        let result = clone_git::run(
-            template.template.git_path.as_ref().unwrap().as_str(),
-            path_in_template,
-        );
+        clone_template(template, path)?;
+    } else if path.is_file() {
+        return Err(DmanError::InvalidInput(format!("Path {path:?} is a file")));
+    } else if path.read_dir()?.next().is_none() {
+        println!("Folder {path:?} exists but is empty");
+        question_yes_no!("Clone into it?");
 
-        match result {
-            Ok(_) => {
-                println!("Cloned template from Git repository");
+        clone_template(template, path)?;
+    }
 
-                // Repeat this function
-                write_template_to_fs(template.template.clone(), template_folder);
+    Ok(())
+}
 
-                return;
-            }
-            Err(error) => {
-                println!("Error: {error:?}");
-                pretty_panic!("Could not clone template from Git repository");
-            }
-        }
-    } else if path_in_template.is_file() {
-        pretty_panic!("Path is a file");
-    } else if path_in_template.is_dir() {
-        // Check if folder is empty
-        if path_in_template.read_dir().unwrap().next().is_none() {
-            println!("Folder {path_in_template:?} exists but is empty");
-            question_yes_no!("Clone into it?");
-
-            let result = clone_git::run(
-                template.template.git_path.as_ref().unwrap().as_str(),
-                path_in_template,
-            );
-
-            match result {
-                Ok(_) => {
-                    println!("Cloned template from Git repository");
-
-                    // Repeat this function
-                    write_template_to_fs(template.template.clone(), template_folder);
-
-                    return;
-                }
-                Err(error) => {
-                    println!("Error: {error:?}");
-                    pretty_panic!("Could not clone template from Git repository");
-                }
-            }
-        }
-    }
+/// Check that path of template is a Git repository with remote of template,
+/// and that git path of template exists
+///
+/// # Errors
+///
+/// * If path isn't a Git repository
+/// * If repository doesn't have remote of template, or it doesn't point to git path
+/// * If git path can't be connected to
+pub fn check_template(template: &Template, path: &Path) -> Result<(), DmanError> {
+    let git_path = &template.git_path;
 
     // Check if path defined in template is a git repository
-    let repo = match git2::Repository::open(path_in_template) {
+    let repo = match git2::Repository::open(path) {
         Ok(repo) => repo,
         Err(_) => {
             // Check if folder is empty
-            if path_in_template.read_dir().unwrap().next().is_some() {
-                pretty_panic!(
-                    "Path: {path_in_template:?} isn't empty, make sure it's empty before cloning"
-                );
+            if path.read_dir()?.next().is_some() {
+                return Err(DmanError::InvalidInput(format!(
+                    "Path: {path:?} isn't empty, make sure it's empty before cloning"
+                )));
             }
 
-            pretty_panic!("Path: {path_in_template:?} is not a git repository");
+            return Err(DmanError::InvalidInput(format!(
+                "Path: {path:?} is not a git repository"
+            )));
         }
     };
 
-    match repo.find_remote("origin") {
-        Ok(_) => {
-            println!("Path: Remote origin exists");
+    let remote_name = template.remote_name();
+    match repo.find_remote(remote_name) {
+        Ok(remote) => {
+            println!("Path: Remote {remote_name} exists");
+
+            let url = remote.url().unwrap_or_default();
+            if url != git_path {
+                return Err(DmanError::InvalidInput(format!(
+                    "Remote {remote_name} of {path:?} is {url}, not {git_path}, point it to \
+                     {git_path} with `git remote set-url {remote_name} {git_path}` or, \
+                     if template is registered, with `dman doctor --fix`, \
+                     or change git path of template with `dman edit <name> --git-path {url}`"
+                )));
+            }
         }
         Err(_) => {
-            pretty_panic!("Path: Remote origin does not exist");
+            return Err(DmanError::NotFound(format!(
+                "Remote {remote_name} of {path:?}"
+            )));
         }
     }
 
     // Check if git path defined in template exists
-    check_if_remote_exists(template.template.git_path.unwrap());
+    check_if_remote_exists(git_path.clone())
+}
+
+/// Write template to filesystem
+fn write_template_to_fs(mut template: Template, template_folder: String) -> Result<(), DmanError> {
+    // Replace ~ with home path
+    // this is needed because ~ is not expanded by the std::path::Path
+    // Only path is expanded, other fields like git_path can contain ~ too
+    template.path = expand_home(&template.path).to_string_lossy().to_string();
+    let name = &template.name;
+
+    // Create file path
+    let template_path_string = template_folder + "/" + name + ".toml";
+    let template_path = Path::new(&template_path_string);
+
+    // Check if template already exists
+    if template_path.exists() {
+        return Err(DmanError::AlreadyExists(format!("Template {name:?}")));
+    }
+
+    // Check if path defined in template exists
+    let path_in_template = Path::new(&template.path);
+
+    clone_if_missing(&template, path_in_template)?;
+    check_template(&template, path_in_template)?;
 
     // Write template to fs ~/.config/dotfile-manager/templates/foo.toml
-    let result = fs::write(template_path, toml);
+    write_template_file(template_path, &template)?;
+    println!("Template {name:?} created");
 
-    // Print result
-    println!("{result:?}");
+    Ok(())
 }
@@ -0,0 +1,638 @@
+#[path = "./render.rs"]
+pub mod render;
+#[path = "./deploy_state.rs"]
+pub mod state;
+
+use crate::args::{
+    backup::snapshot,
+    secret::{decrypt_secrets, SECRET_EXTENSION},
+};
+use crate::lib;
+use lib::{
+    expand_home, machine::Machine, match_user_input_with_existing_templates, DeployFile,
+    DeployMode, DmanError, Template,
+};
+use mytools::warn;
+use question::{Answer, Question};
+use render::{is_templated, rendered_target, Renderer};
+use state::{hash_bytes, hash_file, CopiedFile, DeployState};
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use walkdir::WalkDir;
+
+/// Suffix of backups of files, that were replaced by deployed files
+const BACKUP_SUFFIX: &str = "dman-backup";
+
+/// What to do with file, that already exists at target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnExisting {
+    /// Rename existing file to `<target>.dman-backup`
+    Backup,
+    /// Leave existing file and don't deploy
+    Skip,
+    /// Remove existing file
+    Overwrite,
+}
+
+impl FromStr for OnExisting {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "backup" | "b" => Ok(OnExisting::Backup),
+            "skip" | "s" => Ok(OnExisting::Skip),
+            "overwrite" | "o" => Ok(OnExisting::Overwrite),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown action {s:?}, use backup, skip or overwrite"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OnExisting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnExisting::Backup => write!(f, "backup"),
+            OnExisting::Skip => write!(f, "skip"),
+            OnExisting::Overwrite => write!(f, "overwrite"),
+        }
+    }
+}
+
+/// What happened with deployed file
+enum Deployed {
+    /// File was linked or copied
+    Done,
+    /// Target is already up to date
+    UpToDate,
+    /// Existing target was kept
+    Skipped,
+}
+
+/// Ask user what to do with existing file, skip is the default
+fn ask_on_existing(target: &Path) -> OnExisting {
+    let answer = Question::new(&format!(
+        "{target:?} already exists, back it up, skip or overwrite it?"
+    ))
+    .acceptable(vec!["backup", "skip", "overwrite", "b", "s", "o"])
+    .until_acceptable()
+    .default(Answer::RESPONSE("skip".to_string()))
+    .show_defaults()
+    .ask();
+
+    match answer {
+        Some(Answer::RESPONSE(action)) => action.parse().unwrap_or(OnExisting::Skip),
+        _ => OnExisting::Skip,
+    }
+}
+
+/// Path of backup of target, number is added if backup already exists
+fn backup_path(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut backup = target.with_file_name(format!("{file_name}.{BACKUP_SUFFIX}"));
+    let mut number = 1;
+    while backup.symlink_metadata().is_ok() {
+        backup = target.with_file_name(format!("{file_name}.{BACKUP_SUFFIX}.{number}"));
+        number += 1;
+    }
+
+    backup
+}
+
+/// Put back the file, that was backed up when target was deployed
+///
+/// Only backup recorded by `link` is restored, other files named like backups are left alone
+fn restore_backup(target: &Path, backups: &mut BTreeMap<String, String>) -> Result<(), DmanError> {
+    let key = target.to_string_lossy().to_string();
+    let Some(backup) = backups.get(&key).map(PathBuf::from) else {
+        return Ok(());
+    };
+
+    if backup.symlink_metadata().is_ok() {
+        fs::rename(&backup, target)?;
+        println!("Restored {target:?} from {backup:?}");
+    } else {
+        warn!("Backup {backup:?} of {target:?} doesn't exist anymore");
+    }
+    backups.remove(&key);
+
+    Ok(())
+}
+
+/// Remove file, folder or symlink, symlinked folders aren't followed
+fn remove_path(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Make room for deployed file, returns false if user wants to keep existing target
+///
+/// Path of backup is recorded in `backups`, so `unlink` restores exactly that file
+fn replace_existing(
+    target: &Path,
+    on_existing: Option<OnExisting>,
+    backups: &mut BTreeMap<String, String>,
+) -> Result<bool, DmanError> {
+    match on_existing.unwrap_or_else(|| ask_on_existing(target)) {
+        OnExisting::Skip => return Ok(false),
+        OnExisting::Backup => {
+            let backup = backup_path(target);
+            fs::rename(target, &backup)?;
+            println!("Backed up {target:?} to {backup:?}");
+            backups.insert(
+                target.to_string_lossy().to_string(),
+                backup.to_string_lossy().to_string(),
+            );
+        }
+        OnExisting::Overwrite => {
+            if let Some(id) = snapshot("deploy", "link --on-existing overwrite", &[target.into()])?
+            {
+                println!("Backed up {target:?} as {id}");
+            }
+            remove_path(target)?;
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, target: &Path) -> io::Result<()> {
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(source, target)
+    } else {
+        std::os::windows::fs::symlink_file(source, target)
+    }
+}
+
+/// Check if target is symlink pointing to source
+fn is_linked(source: &Path, target: &Path) -> bool {
+    fs::read_link(target).is_ok_and(|link| link == source)
+}
+
+/// Get source in repository and target of deployed file
+fn file_paths(template: &Template, file: &DeployFile) -> (PathBuf, PathBuf) {
+    (
+        expand_home(&template.path).join(&file.source),
+        expand_home(&file.target),
+    )
+}
+
+/// Get source and target of every file inside deployed file, that can be a folder
+///
+/// Git metadata and encrypted secrets aren't deployed
+fn copied_files(source: &Path, target: &Path) -> Result<Vec<(PathBuf, PathBuf)>, DmanError> {
+    if !source.is_dir() {
+        return Ok(vec![(source.to_path_buf(), target.to_path_buf())]);
+    }
+
+    let mut files = Vec::new();
+    let walker = WalkDir::new(source)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+
+    for entry in walker {
+        let entry = entry.map_err(io::Error::from)?;
+        // Encrypted secrets are deployed as their decrypted files
+        if entry.file_type().is_dir()
+            || entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == SECRET_EXTENSION)
+        {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+        files.push((entry.path().to_path_buf(), target.join(relative)));
+    }
+
+    Ok(files)
+}
+
+/// Get files of template, fails if template is used in place
+fn files_to_deploy(template: &Template) -> Result<&[DeployFile], DmanError> {
+    if template.deploy == DeployMode::InPlace || template.files.is_empty() {
+        return Err(DmanError::InvalidInput(format!(
+            "Template {} doesn't have files to deploy, set deploy = \"symlink\" or \"copy\" \
+             and [[template.files]] in its template file",
+            template.name
+        )));
+    }
+
+    Ok(&template.files)
+}
+
+fn link_file(
+    source: &Path,
+    target: &Path,
+    on_existing: Option<OnExisting>,
+    backups: &mut BTreeMap<String, String>,
+) -> Result<Deployed, DmanError> {
+    // Broken symlink doesn't exist, but it still has to be replaced
+    if target.symlink_metadata().is_ok() {
+        if is_linked(source, target) {
+            return Ok(Deployed::UpToDate);
+        }
+
+        if !replace_existing(target, on_existing, backups)? {
+            return Ok(Deployed::Skipped);
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    symlink(source, target)?;
+
+    Ok(Deployed::Done)
+}
+
+/// Copy file to target and record hash of its contents
+///
+/// `rendered` are contents of templated source, that are written instead of source
+/// Target, that wasn't changed since last deploy, is overwritten without asking
+fn copy_file(
+    source: &Path,
+    target: &Path,
+    rendered: Option<&str>,
+    copies: &mut BTreeMap<String, CopiedFile>,
+    on_existing: Option<OnExisting>,
+    backups: &mut BTreeMap<String, String>,
+) -> Result<Deployed, DmanError> {
+    let key = target.to_string_lossy().to_string();
+    let copy = CopiedFile {
+        source: source.to_string_lossy().to_string(),
+        hash: match rendered {
+            Some(rendered) => hash_bytes(rendered.as_bytes()),
+            None => hash_file(source)?,
+        },
+        source_hash: match rendered {
+            Some(_) => Some(hash_file(source)?),
+            None => None,
+        },
+    };
+
+    if let Ok(metadata) = target.symlink_metadata() {
+        // Symlink has to be replaced, copying into it would write into the file it points to
+        let target_hash = if metadata.is_file() {
+            Some(hash_file(target)?)
+        } else {
+            None
+        };
+
+        if target_hash.as_ref() == Some(&copy.hash) {
+            copies.insert(key, copy);
+            return Ok(Deployed::UpToDate);
+        }
+
+        let unchanged = copies
+            .get(&key)
+            .is_some_and(|previous| target_hash.as_ref() == Some(&previous.hash));
+
+        if !unchanged && !replace_existing(target, on_existing, backups)? {
+            return Ok(Deployed::Skipped);
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match rendered {
+        Some(rendered) => fs::write(target, rendered)?,
+        None => {
+            fs::copy(source, target)?;
+        }
+    }
+
+    copies.insert(key, copy);
+
+    Ok(Deployed::Done)
+}
+
+/// Get renderer for templated files of template, it's created on first use
+fn get_renderer<'a>(
+    renderer: &'a mut Option<Renderer>,
+    template: &Template,
+) -> Result<&'a Renderer, DmanError> {
+    let loaded = match renderer.take() {
+        Some(loaded) => loaded,
+        None => Renderer::new(&expand_home(&template.path), &Machine::current()?)?,
+    };
+
+    Ok(renderer.insert(loaded))
+}
+
+/// Deploy one file of template, folders are copied file by file
+///
+/// Templated files are rendered and copied, even if template is deployed as symlinks
+fn deploy_file(
+    template: &Template,
+    file: &DeployFile,
+    state: &mut DeployState,
+    renderer: &mut Option<Renderer>,
+    on_existing: Option<OnExisting>,
+) -> usize {
+    let (source, target) = file_paths(template, file);
+
+    if !source.exists() {
+        warn!("Couldn't deploy {target:?}: Source {source:?} not found");
+        return 1;
+    }
+
+    let pairs = match template.deploy {
+        DeployMode::Copy => copied_files(&source, &target),
+        _ => Ok(vec![(source, target.clone())]),
+    };
+    let pairs = match pairs {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            warn!("Couldn't deploy {target:?}: {e}");
+            return 1;
+        }
+    };
+
+    let mut failed = 0;
+    let mut backups = BTreeMap::new();
+    for (source, target) in pairs {
+        let templated = is_templated(&source);
+        let target = if templated {
+            rendered_target(&target)
+        } else {
+            target
+        };
+
+        let (verb, result) = if templated {
+            let result = get_renderer(renderer, template)
+                .and_then(|renderer| renderer.render(&source))
+                .and_then(|rendered| {
+                    copy_file(
+                        &source,
+                        &target,
+                        Some(&rendered),
+                        state.template_mut(&template.name),
+                        on_existing,
+                        &mut backups,
+                    )
+                });
+            ("Rendered", result)
+        } else if template.deploy == DeployMode::Copy {
+            let result = copy_file(
+                &source,
+                &target,
+                None,
+                state.template_mut(&template.name),
+                on_existing,
+                &mut backups,
+            );
+            ("Copied", result)
+        } else {
+            (
+                "Linked",
+                link_file(&source, &target, on_existing, &mut backups),
+            )
+        };
+
+        match result {
+            Ok(Deployed::Done) => println!("{verb} {source:?} to {target:?}"),
+            Ok(Deployed::UpToDate) => println!("{target:?} is up to date"),
+            Ok(Deployed::Skipped) => println!("Skipped {target:?}, it already exists"),
+            Err(e) => {
+                warn!("Couldn't deploy {target:?}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if !backups.is_empty() {
+        state
+            .backups
+            .entry(template.name.clone())
+            .or_default()
+            .extend(backups);
+    }
+
+    failed
+}
+
+/// Deploy files of template to their targets, as symlinks or copies
+///
+/// `on_existing` decides what happens with files already at targets, user is asked
+/// for every file if it's None
+/// Files ending with .tmpl are rendered with variables of this machine and copied
+/// Secrets of template are decrypted first
+/// Hashes of copies are recorded, so `dman drift` can find out which of them were changed
+///
+/// # Errors
+///
+/// * If template is used in place
+/// * If any file couldn't be deployed, the rest is deployed anyway
+pub fn link(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    on_existing: Option<OnExisting>,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+    decrypt_secrets(&template)?;
+    let mut state = DeployState::load()?;
+    let mut renderer = None;
+    let mut failed = 0;
+
+    for file in files_to_deploy(&template)? {
+        failed += deploy_file(&template, file, &mut state, &mut renderer, on_existing);
+    }
+
+    if state
+        .templates
+        .get(&template.name)
+        .is_some_and(|copies| !copies.is_empty())
+        || state.backups.contains_key(&template.name)
+    {
+        state.save()?;
+    }
+
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!(
+            "{failed} file(s) of {} couldn't be deployed",
+            template.name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Remove copy, that wasn't changed since it was deployed
+///
+/// Returns false if copy was changed and was kept
+fn remove_copy(target: &Path, copy: &CopiedFile) -> Result<bool, DmanError> {
+    if !target.is_file() {
+        return Ok(true);
+    }
+
+    if hash_file(target)? != copy.hash {
+        return Ok(false);
+    }
+
+    fs::remove_file(target)?;
+
+    Ok(true)
+}
+
+/// Remove copies of template recorded in state file
+///
+/// Returns number of copies, that couldn't be removed
+fn remove_copies(template: &Template, state: &mut DeployState) -> Result<usize, DmanError> {
+    let Some(copies) = state.templates.get_mut(&template.name) else {
+        return Ok(0);
+    };
+    let backups = state.backups.entry(template.name.clone()).or_default();
+    let mut failed = 0;
+
+    for (target, copy) in copies.clone() {
+        let target_path = Path::new(&target);
+
+        match remove_copy(target_path, &copy) {
+            Ok(true) => {
+                println!("Removed {target:?}");
+                copies.remove(&target);
+
+                if let Err(e) = restore_backup(target_path, backups) {
+                    warn!("Couldn't restore backup of {target:?}: {e}");
+                    failed += 1;
+                }
+            }
+            Ok(false) => warn!(
+                "{target:?} was changed since it was deployed, keeping it, \
+                 see `dman drift {}`",
+                template.name
+            ),
+            Err(e) => {
+                warn!("Couldn't remove {target:?}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if copies.is_empty() {
+        state.templates.remove(&template.name);
+    }
+
+    Ok(failed)
+}
+
+/// Remove files deployed by `link` and restore backed up files
+///
+/// Symlinks, that don't point to template, and copies changed since they were deployed
+/// are left alone
+///
+/// # Errors
+///
+/// * If template is used in place
+/// * If any file couldn't be removed, the rest is removed anyway
+pub fn unlink(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+    let files = files_to_deploy(&template)?;
+    let mut state = DeployState::load()?;
+    let mut failed = remove_copies(&template, &mut state)?;
+    let backups = state.backups.entry(template.name.clone()).or_default();
+
+    if template.deploy == DeployMode::Symlink {
+        for file in files {
+            let (source, target) = file_paths(&template, file);
+
+            // Rendered files were copied
+            if is_templated(&source) {
+                continue;
+            }
+
+            if !is_linked(&source, &target) {
+                println!("{target:?} isn't linked to {}, skipping", template.name);
+                continue;
+            }
+
+            if let Err(e) = fs::remove_file(&target) {
+                warn!("Couldn't unlink {target:?}: {e}");
+                failed += 1;
+                continue;
+            }
+            println!("Unlinked {target:?}");
+
+            if let Err(e) = restore_backup(&target, backups) {
+                warn!("Couldn't restore backup of {target:?}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if backups.is_empty() {
+        state.backups.remove(&template.name);
+    }
+    state.save()?;
+
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!(
+            "{failed} file(s) of {} couldn't be removed",
+            template.name
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backup_path, restore_backup};
+    use std::{collections::BTreeMap, fs};
+
+    #[test]
+    fn only_recorded_backup_is_restored() {
+        let folder = std::env::temp_dir().join(format!("dman-deploy-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let target = folder.join("vimrc");
+        fs::write(folder.join("vimrc.dman-backup"), "user's own file").unwrap();
+
+        let backup = backup_path(&target);
+        assert_eq!(backup, folder.join("vimrc.dman-backup.1"));
+        fs::write(&backup, "backed up").unwrap();
+
+        let mut backups = BTreeMap::new();
+        restore_backup(&target, &mut backups).unwrap();
+        assert!(!target.exists());
+
+        backups.insert(
+            target.to_string_lossy().to_string(),
+            backup.to_string_lossy().to_string(),
+        );
+        restore_backup(&target, &mut backups).unwrap();
+        let restored = fs::read_to_string(&target).unwrap();
+        let untouched = fs::read_to_string(folder.join("vimrc.dman-backup")).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(restored, "backed up");
+        assert_eq!(untouched, "user's own file");
+        assert!(backups.is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::lib;
+use lib::{get_data_folder, DmanError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// File copied to its target by `dman link`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CopiedFile {
+    /// File in repository it was copied from
+    pub source: String,
+    /// SHA-256 of contents at the time it was copied
+    pub hash: String,
+    /// SHA-256 of templated source, whose contents differ from rendered copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+}
+
+/// Copies deployed by dman, stored in ~/.local/share/dotfile-manager/deployed.toml
+///
+/// Copies and backups are grouped by template name and keyed by target path
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeployState {
+    #[serde(default)]
+    pub templates: BTreeMap<String, BTreeMap<String, CopiedFile>>,
+    /// Paths of backups of files, that were at targets before deploy
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub backups: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+fn state_file_path() -> Result<PathBuf, DmanError> {
+    Ok(get_data_folder()?.join("deployed.toml"))
+}
+
+impl DeployState {
+    /// Load state file, missing file means nothing was deployed yet
+    pub fn load() -> Result<Self, DmanError> {
+        let path = state_file_path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|source| DmanError::TomlParse {
+            file: path.to_string_lossy().to_string(),
+            source,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), DmanError> {
+        let contents = toml::to_string(self)
+            .map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize state: {e}")))?;
+
+        fs::write(state_file_path()?, contents)?;
+
+        Ok(())
+    }
+
+    /// Copies of template, created if template has none yet
+    pub fn template_mut(&mut self, template: &str) -> &mut BTreeMap<String, CopiedFile> {
+        self.templates.entry(template.to_string()).or_default()
+    }
+
+    /// Move copies and backups of template under its new name
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(copies) = self.templates.remove(from) {
+            self.templates.insert(to.to_string(), copies);
+        }
+        if let Some(backups) = self.backups.remove(from) {
+            self.backups.insert(to.to_string(), backups);
+        }
+    }
+
+    /// Point sources of copies of template into new path of its checkout
+    pub fn move_sources(&mut self, template: &str, from: &Path, to: &Path) {
+        let Some(copies) = self.templates.get_mut(template) else {
+            return;
+        };
+
+        for copy in copies.values_mut() {
+            if let Ok(relative) = Path::new(&copy.source).strip_prefix(from) {
+                copy.source = to.join(relative).to_string_lossy().to_string();
+            }
+        }
+    }
+}
+
+fn finish_hash(hasher: Sha256) -> String {
+    let mut hash = String::new();
+    for byte in hasher.finalize() {
+        // Writing into String can't fail
+        let _ = write!(hash, "{byte:02x}");
+    }
+
+    hash
+}
+
+/// Get SHA-256 of file contents as hex string
+pub fn hash_file(path: &Path) -> Result<String, DmanError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(finish_hash(hasher))
+}
+
+/// Get SHA-256 of contents, that aren't written yet, as hex string
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+
+    finish_hash(hasher)
+}
@@ -0,0 +1,437 @@
+use crate::args::{create::clone_template, list::style_table};
+use crate::lib;
+use git2::{build::CheckoutBuilder, Repository};
+use lib::{
+    check_if_remote_exists, expand_home, is_offline, machine::Machine, read_template_file,
+    remote::pending_remotes, save_template, DmanError, Template,
+};
+use mytools::env::get_home_folder;
+use owo_colors::OwoColorize;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use tabled::{Table, Tabled};
+
+/// Result of one check
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Fixed,
+    Warning,
+    Failed,
+    Skipped,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "{}", "ok".green()),
+            Status::Fixed => write!(f, "{}", "fixed".green()),
+            Status::Warning => write!(f, "{}", "warning".yellow()),
+            Status::Failed => write!(f, "{}", "failed".red()),
+            Status::Skipped => write!(f, "{}", "skipped".dimmed()),
+        }
+    }
+}
+
+/// Row of table printed by doctor
+#[derive(Tabled)]
+struct CheckRow {
+    subject: String,
+    check: String,
+    status: Status,
+    details: String,
+}
+
+/// Checks, that were run, with repairs if `fix` is set
+struct Doctor {
+    fix: bool,
+    rows: Vec<CheckRow>,
+}
+
+impl Doctor {
+    fn report(&mut self, subject: &str, check: &str, status: Status, details: impl Into<String>) {
+        self.rows.push(CheckRow {
+            subject: subject.to_string(),
+            check: check.to_string(),
+            status,
+            details: details.into(),
+        });
+    }
+
+    /// Repair problem with --fix, otherwise report that --fix repairs it
+    ///
+    /// `fix` returns what was done
+    ///
+    /// Returns true if problem was fixed
+    fn repair(
+        &mut self,
+        subject: &str,
+        check: &str,
+        problem: String,
+        fix: impl FnOnce() -> Result<String, DmanError>,
+    ) -> bool {
+        if !self.fix {
+            self.report(
+                subject,
+                check,
+                Status::Failed,
+                format!("{problem}, --fix repairs it"),
+            );
+            return false;
+        }
+
+        match fix() {
+            Ok(done) => {
+                self.report(subject, check, Status::Fixed, format!("{problem}, {done}"));
+                true
+            }
+            Err(e) => {
+                self.report(
+                    subject,
+                    check,
+                    Status::Failed,
+                    format!("{problem}, couldn't fix it: {e}"),
+                );
+                false
+            }
+        }
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.rows.iter().filter(|row| row.status == status).count()
+    }
+}
+
+/// Get ~/.config/dotfile-manager/templates/, without creating it
+fn templates_folder() -> PathBuf {
+    Path::new(&get_home_folder()).join(".config/dotfile-manager/templates")
+}
+
+/// Check templates folder
+fn check_setup(doctor: &mut Doctor) {
+    const SUBJECT: &str = "dman";
+
+    let templates = templates_folder();
+    if templates.is_dir() {
+        doctor.report(
+            SUBJECT,
+            "templates folder",
+            Status::Ok,
+            templates.to_string_lossy(),
+        );
+    } else {
+        doctor.repair(
+            SUBJECT,
+            "templates folder",
+            format!("{templates:?} doesn't exist"),
+            || {
+                fs::create_dir_all(&templates)?;
+                Ok("created it".to_string())
+            },
+        );
+    }
+}
+
+/// Check that path of template exists, missing repository is cloned with --fix
+///
+/// Returns false if path can't be checked further
+fn check_path(doctor: &mut Doctor, template: &Template, path: &Path) -> bool {
+    let name = &template.name;
+    let is_empty = path.read_dir().map_or(true, |mut dir| dir.next().is_none());
+
+    if path.is_file() {
+        doctor.report(name, "path", Status::Failed, format!("{path:?} is a file"));
+        return false;
+    }
+
+    if !is_empty {
+        doctor.report(name, "path", Status::Ok, path.to_string_lossy());
+        return true;
+    }
+
+    let problem = if path.exists() {
+        format!("{path:?} is empty")
+    } else {
+        format!("{path:?} doesn't exist")
+    };
+    if template.git_path.is_empty() {
+        doctor.report(
+            name,
+            "path",
+            Status::Failed,
+            format!("{problem}, template has no git_path to clone it from"),
+        );
+        return false;
+    }
+
+    doctor.repair(name, "path", problem, || {
+        fs::create_dir_all(path)?;
+        clone_template(template, path)?;
+        Ok(format!("cloned {}", template.git_path))
+    })
+}
+
+/// Check that remote of template exists and points to git_path
+///
+/// Returns false if remote can't be used
+fn check_remote(doctor: &mut Doctor, template: &Template, repo: &Repository) -> bool {
+    let name = &template.name;
+    let remote_name = template.remote_name();
+    let check = format!("remote {remote_name}");
+
+    let url = match repo.find_remote(remote_name) {
+        Ok(remote) => remote.url().unwrap_or_default().to_string(),
+        Err(_) if template.git_path.is_empty() => {
+            doctor.report(
+                name,
+                &check,
+                Status::Failed,
+                "remote doesn't exist and template has no git_path",
+            );
+            return false;
+        }
+        Err(_) => {
+            return doctor.repair(name, &check, "remote doesn't exist".to_string(), || {
+                repo.remote(remote_name, &template.git_path)?;
+                Ok(format!("added it with {}", template.git_path))
+            });
+        }
+    };
+
+    if template.git_path.is_empty() {
+        doctor.repair(name, &check, "template has no git_path".to_string(), || {
+            let template = Template {
+                git_path: url.clone(),
+                ..template.clone()
+            };
+            save_template(&template)?;
+            Ok(format!("set it to {url}"))
+        });
+    } else if url != template.git_path {
+        doctor.repair(
+            name,
+            &check,
+            format!("URL is {url}, template has {}", template.git_path),
+            || {
+                repo.remote_set_url(remote_name, &template.git_path)?;
+                Ok(format!("reset it to {}", template.git_path))
+            },
+        );
+    } else {
+        doctor.report(name, &check, Status::Ok, url);
+    }
+
+    true
+}
+
+/// Check that branch is checked out, the one set in template if there's one
+fn check_branch(doctor: &mut Doctor, template: &Template, repo: &Repository) {
+    let name = &template.name;
+
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) => {
+            doctor.report(
+                name,
+                "branch",
+                Status::Failed,
+                format!("HEAD is invalid, {}", e.message()),
+            );
+            return;
+        }
+    };
+
+    let current = head
+        .is_branch()
+        .then(|| head.shorthand().unwrap_or_default().to_string());
+    let Some(expected) = &template.branch else {
+        match current {
+            Some(current) => doctor.report(name, "branch", Status::Ok, current),
+            None => doctor.report(
+                name,
+                "branch",
+                Status::Warning,
+                "HEAD is detached, set branch in template, so --fix can check it out",
+            ),
+        }
+        return;
+    };
+
+    if current.as_ref() == Some(expected) {
+        doctor.report(name, "branch", Status::Ok, expected);
+        return;
+    }
+
+    let refname = format!("refs/heads/{expected}");
+    if repo.find_reference(&refname).is_err() {
+        doctor.report(
+            name,
+            "branch",
+            Status::Failed,
+            format!("branch {expected} doesn't exist"),
+        );
+        return;
+    }
+
+    let problem = match current {
+        Some(current) => format!("{current} is checked out instead of {expected}"),
+        None => format!("HEAD is detached instead of {expected}"),
+    };
+    doctor.repair(name, "branch", problem, || {
+        // Safe checkout fails instead of overwriting local changes
+        repo.checkout_tree(
+            &repo.revparse_single(&refname)?,
+            Some(CheckoutBuilder::new().safe()),
+        )?;
+        repo.set_head(&refname)?;
+        Ok(format!("checked out {expected}"))
+    });
+}
+
+/// Check that remote can be connected to
+fn check_reachable(doctor: &mut Doctor, template: &Template, repo: &Repository) {
+    let name = &template.name;
+    if is_offline() {
+        doctor.report(name, "remote reachable", Status::Skipped, "offline");
+        return;
+    }
+
+    let result = repo
+        .find_remote(template.remote_name())
+        .map_err(DmanError::from)
+        .and_then(|remote| check_if_remote_exists(remote.url().unwrap_or_default().to_string()));
+
+    match result {
+        Ok(()) => doctor.report(name, "remote reachable", Status::Ok, ""),
+        Err(e) => doctor.report(name, "remote reachable", Status::Failed, e.to_string()),
+    }
+}
+
+/// Check remotes, whose check was deferred in offline mode, remotes of templates were
+/// already checked with templates
+fn check_pending(doctor: &mut Doctor) {
+    const SUBJECT: &str = "remote";
+
+    for remote in pending_remotes() {
+        if is_offline() {
+            doctor.report(
+                SUBJECT,
+                "pending check",
+                Status::Warning,
+                format!("{remote} isn't checked yet, run doctor online"),
+            );
+            continue;
+        }
+
+        match check_if_remote_exists(remote.clone()) {
+            Ok(()) => doctor.report(SUBJECT, "pending check", Status::Ok, remote),
+            Err(e) => doctor.report(
+                SUBJECT,
+                "pending check",
+                Status::Failed,
+                format!("{remote}: {e}"),
+            ),
+        }
+    }
+}
+
+fn check_template(doctor: &mut Doctor, template: &Template) {
+    let path = expand_home(&template.path);
+    if !check_path(doctor, template, &path) {
+        return;
+    }
+
+    let repo = match Repository::open(&path) {
+        Ok(repo) => repo,
+        Err(_) => {
+            doctor.report(
+                &template.name,
+                "git repository",
+                Status::Failed,
+                format!("{path:?} isn't a Git repository"),
+            );
+            return;
+        }
+    };
+
+    if !check_remote(doctor, template, &repo) {
+        return;
+    }
+    check_branch(doctor, template, &repo);
+    check_reachable(doctor, template, &repo);
+}
+
+/// Check setup of dman and every template, print table of results
+///
+/// Templates, that are disabled or for other machines, are skipped
+/// Remotes, whose check was deferred in offline mode, are checked too
+/// With `fix`, problems are repaired where possible: missing folders are created,
+/// missing repositories cloned, remote URL reset to git_path, branch of template checked out
+///
+/// # Errors
+///
+/// * If any check failed
+pub fn doctor(fix: bool) -> Result<(), DmanError> {
+    let mut doctor = Doctor {
+        fix,
+        rows: Vec::new(),
+    };
+
+    check_setup(&mut doctor);
+
+    let machine = Machine::current()?;
+    let mut files: Vec<PathBuf> = match fs::read_dir(templates_folder()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().and_then(|extension| extension.to_str()) == Some("toml")
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+
+    for file in files {
+        let template = match read_template_file(&file) {
+            Ok((_, template)) => template,
+            Err(e) => {
+                let name = file.file_stem().unwrap_or_default().to_string_lossy();
+                doctor.report(&name, "template file", Status::Failed, e.to_string());
+                continue;
+            }
+        };
+
+        if !template.enabled || !template.applies_to(&machine) {
+            doctor.report(
+                &template.name,
+                "template",
+                Status::Skipped,
+                "disabled or for other machine",
+            );
+            continue;
+        }
+
+        check_template(&mut doctor, &template);
+    }
+
+    check_pending(&mut doctor);
+
+    let mut table = Table::new(&doctor.rows);
+    style_table(&mut table);
+    println!("{table}");
+
+    let failed = doctor.count(Status::Failed);
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!("{failed} check(s) failed")));
+    }
+
+    let warnings = doctor.count(Status::Warning);
+    if warnings > 0 {
+        println!("{warnings} warning(s)");
+    }
+
+    Ok(())
+}
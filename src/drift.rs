@@ -0,0 +1,211 @@
+use crate::args::{
+    backup::snapshot,
+    deploy::state::{hash_file, CopiedFile, DeployState},
+    list::style_table,
+};
+use crate::lib;
+use lib::{match_user_input_with_existing_templates, DmanError};
+use owo_colors::OwoColorize;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use tabled::{Table, Tabled};
+
+/// How deployed copy differs from the last deploy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Drift {
+    /// Copy was edited
+    Modified,
+    /// Copy was removed
+    Deleted,
+    /// File in repository was changed, but it wasn't deployed again
+    Outdated,
+    /// Both copy and file in repository were changed
+    Diverged,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Modified => write!(f, "modified"),
+            Drift::Deleted => write!(f, "deleted"),
+            Drift::Outdated => write!(f, "outdated"),
+            Drift::Diverged => write!(f, "diverged"),
+        }
+    }
+}
+
+/// Row of drift table
+#[derive(Tabled)]
+struct DriftRow {
+    template: String,
+    target: String,
+    drift: String,
+}
+
+/// Compare copy and its source with hash recorded when it was deployed
+fn check_copy(target: &Path, copy: &CopiedFile) -> Result<Option<Drift>, DmanError> {
+    if !target.is_file() {
+        return Ok(Some(Drift::Deleted));
+    }
+
+    let source = Path::new(&copy.source);
+    let target_changed = hash_file(target)? != copy.hash;
+    // Rendered copy differs from its source, so source is compared with its own hash
+    let source_hash = copy.source_hash.as_ref().unwrap_or(&copy.hash);
+    // Removed source is a change in repository too
+    let source_changed = !source.is_file() || hash_file(source)? != *source_hash;
+
+    Ok(match (target_changed, source_changed) {
+        (false, false) => None,
+        (true, false) => Some(Drift::Modified),
+        (false, true) => Some(Drift::Outdated),
+        (true, true) => Some(Drift::Diverged),
+    })
+}
+
+/// Copy edited copy back into repository and record it as deployed
+///
+/// File in repository can have uncommitted changes, so it's backed up first
+fn pull_back_copy(template: &str, target: &Path, copy: &mut CopiedFile) -> Result<(), DmanError> {
+    snapshot(
+        template,
+        "drift --pull-back",
+        &[PathBuf::from(&copy.source)],
+    )?;
+    fs::copy(target, &copy.source)?;
+    copy.hash = hash_file(target)?;
+
+    Ok(())
+}
+
+/// Report copies deployed by `dman link`, that changed since they were deployed
+///
+/// If no template is given, copies of every template are checked
+/// With `pull_back`, modified copies are copied back into repository,
+/// so they can be committed with `dman push`
+/// Rendered copies are never pulled back, their templated source has to be edited instead
+///
+/// # Errors
+///
+/// * If state file can't be read or written
+/// * If copy or its source can't be read
+pub fn drift(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    pull_back: bool,
+) -> Result<(), DmanError> {
+    let only = if name.is_some() || path.is_some() || git_path.is_some() {
+        Some(match_user_input_with_existing_templates(name, path, git_path)?.name)
+    } else {
+        None
+    };
+
+    let mut state = DeployState::load()?;
+    let mut rows: Vec<DriftRow> = Vec::new();
+    let mut found: Vec<Drift> = Vec::new();
+    let mut pulled_back = 0;
+    let mut rendered = 0;
+
+    for (template, copies) in state.templates.iter_mut() {
+        if only.as_ref().is_some_and(|only| only != template) {
+            continue;
+        }
+
+        for (target, copy) in copies.iter_mut() {
+            let Some(drift) = check_copy(Path::new(target), copy)? else {
+                continue;
+            };
+
+            let drift = if drift == Drift::Modified && copy.source_hash.is_some() {
+                rendered += 1;
+                format!("{}, edit {}", drift.yellow(), copy.source)
+            } else if drift == Drift::Modified && pull_back {
+                pull_back_copy(template, Path::new(target), copy)?;
+                pulled_back += 1;
+                "pulled back".green().to_string()
+            } else {
+                found.push(drift);
+                drift.yellow().to_string()
+            };
+
+            rows.push(DriftRow {
+                template: template.clone(),
+                target: target.clone(),
+                drift,
+            });
+        }
+    }
+
+    if rows.is_empty() {
+        println!("{}", "Deployed copies match the last deploy".green());
+        return Ok(());
+    }
+
+    let mut table = Table::new(&rows);
+    style_table(&mut table);
+    println!("{table}");
+
+    if pulled_back > 0 {
+        state.save()?;
+        println!("Pulled {pulled_back} edited copies back, review and push them with `dman push`");
+    }
+
+    if found.contains(&Drift::Modified) {
+        println!("Run `dman drift --pull-back` to copy edited files back into repositories");
+    }
+
+    if rendered > 0 {
+        println!("Rendered copies can't be pulled back, edit their .tmpl sources instead");
+    }
+
+    if found.contains(&Drift::Outdated) || found.contains(&Drift::Deleted) {
+        println!("Outdated and deleted copies are deployed again by `dman link`");
+    }
+
+    if found.contains(&Drift::Diverged) {
+        println!("Diverged copies were changed in both places, merge them by hand");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_copy;
+    use crate::args::deploy::state::{hash_file, CopiedFile, DeployState};
+    use std::fs;
+
+    #[test]
+    fn moved_copy_template_has_no_drift() {
+        let folder = std::env::temp_dir().join(format!("dman-drift-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let (old_path, path) = (folder.join("old"), folder.join("new"));
+        fs::create_dir_all(old_path.join("nvim")).unwrap();
+        fs::write(old_path.join("nvim/init.lua"), "vim.o.number = true").unwrap();
+        let target = folder.join("init.lua");
+        fs::copy(old_path.join("nvim/init.lua"), &target).unwrap();
+
+        let mut state = DeployState::default();
+        state.template_mut("nvim").insert(
+            target.to_string_lossy().to_string(),
+            CopiedFile {
+                source: old_path.join("nvim/init.lua").to_string_lossy().to_string(),
+                hash: hash_file(&target).unwrap(),
+                source_hash: None,
+            },
+        );
+
+        fs::rename(&old_path, &path).unwrap();
+        state.move_sources("nvim", &old_path, &path);
+
+        let copy = &state.templates["nvim"][&*target.to_string_lossy()];
+        assert_eq!(copy.source, path.join("nvim/init.lua").to_string_lossy());
+        let drift = check_copy(&target, copy).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(drift, None);
+    }
+}
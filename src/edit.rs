@@ -0,0 +1,311 @@
+use crate::args::{
+    create::{check_template, clone_if_missing},
+    deploy::{link, state::DeployState},
+    hooks::Hook,
+};
+use crate::lib;
+use git2::Repository;
+use lib::{
+    check_if_remote_exists, expand_home, match_user_input_with_existing_templates, save_template,
+    set_folders, template_to_toml, DeployFile, DeployMode, DmanError, Template,
+};
+use question::{Answer, Question};
+use std::{fs, path::Path};
+
+/// New values of template fields, None keeps the field as it is
+///
+/// Values are passed as they were written by user, empty value removes optional field
+/// and empty list item clears the list
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub name: Option<String>,
+    pub path: Option<String>,
+    pub git_path: Option<String>,
+    pub branch: Option<String>,
+    pub remote: Option<String>,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    pub pull_strategy: Option<String>,
+    pub hosts: Option<Vec<String>>,
+    pub machine_tags: Option<Vec<String>>,
+    pub deploy: Option<String>,
+    /// Files as source:target, they replace all files of template
+    pub files: Option<Vec<String>>,
+    /// Hooks as hook=command, e.g. post_pull=make
+    pub hooks: Vec<String>,
+}
+
+fn optional(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn set_optional(field: &mut Option<String>, value: Option<String>) {
+    if let Some(value) = value {
+        *field = optional(value);
+    }
+}
+
+fn list(values: Vec<String>) -> Vec<String> {
+    values
+        .into_iter()
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn parse_file(file: &str) -> Result<DeployFile, DmanError> {
+    let (source, target) = file
+        .split_once(':')
+        .filter(|(source, target)| !source.is_empty() && !target.is_empty())
+        .ok_or_else(|| DmanError::InvalidInput(format!("File {file:?} has to be source:target")))?;
+
+    Ok(DeployFile {
+        source: source.to_string(),
+        target: target.to_string(),
+    })
+}
+
+impl Changes {
+    /// Apply changes to template
+    fn apply(self, template: &mut Template) -> Result<(), DmanError> {
+        if let Some(name) = self.name {
+            template.name = name;
+        }
+        if let Some(path) = self.path {
+            template.path = path;
+        }
+        if let Some(git_path) = self.git_path {
+            template.git_path = git_path;
+        }
+        set_optional(&mut template.branch, self.branch);
+        set_optional(&mut template.remote, self.remote);
+        set_optional(&mut template.description, self.description);
+        if let Some(tags) = self.tags {
+            template.tags = list(tags);
+        }
+        if let Some(enabled) = self.enabled {
+            template.enabled = enabled;
+        }
+        if let Some(strategy) = self.pull_strategy {
+            template.pull_strategy = optional(strategy)
+                .map(|strategy| strategy.parse())
+                .transpose()?;
+        }
+        if let Some(hosts) = self.hosts {
+            template.hosts = list(hosts);
+        }
+        if let Some(machine_tags) = self.machine_tags {
+            template.machine_tags = list(machine_tags);
+        }
+        if let Some(deploy) = self.deploy {
+            template.deploy = deploy.parse::<DeployMode>()?;
+        }
+        if let Some(files) = self.files {
+            template.files = list(files)
+                .iter()
+                .map(|file| parse_file(file))
+                .collect::<Result<_, _>>()?;
+        }
+        for hook in self.hooks {
+            let (hook, command) = hook.split_once('=').ok_or_else(|| {
+                DmanError::InvalidInput(format!("Hook {hook:?} has to be hook=command"))
+            })?;
+            *hook.parse::<Hook>()?.command_mut(template) = optional(command.to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Check if checkout can be moved or cloned into path
+fn is_free(path: &Path) -> Result<bool, DmanError> {
+    Ok(!path.exists() || (path.is_dir() && path.read_dir()?.next().is_none()))
+}
+
+/// Move checkout of template to new path, parent folders are created
+fn move_repository(from: &Path, to: &Path) -> Result<(), DmanError> {
+    if !is_free(to)? {
+        return Err(DmanError::AlreadyExists(format!(
+            "{to:?}, checkout can't be moved there"
+        )));
+    }
+
+    if to.exists() {
+        fs::remove_dir(to)?;
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)?;
+    println!("Moved {from:?} to {to:?}");
+
+    Ok(())
+}
+
+/// Point remote of repository to git path of template, remote is added if it's missing
+///
+/// Returns previous url of remote, None if remote was added
+fn update_remote(template: &Template, path: &Path) -> Result<Option<String>, DmanError> {
+    let repo = Repository::open(path)?;
+    let remote_name = template.remote_name();
+
+    let previous = match repo.find_remote(remote_name) {
+        Ok(remote) => Some(remote.url().unwrap_or_default().to_string()),
+        Err(_) => None,
+    };
+    match &previous {
+        Some(url) if url == &template.git_path => {}
+        Some(_) => {
+            repo.remote_set_url(remote_name, &template.git_path)?;
+            println!("Remote {remote_name} set to {}", template.git_path);
+        }
+        None => {
+            repo.remote(remote_name, &template.git_path)?;
+            println!("Added remote {remote_name}: {}", template.git_path);
+        }
+    }
+
+    Ok(previous)
+}
+
+/// Undo `update_remote`, remote is pointed back to previous url or removed
+fn restore_remote(
+    template: &Template,
+    path: &Path,
+    previous: Option<String>,
+) -> Result<(), DmanError> {
+    let repo = Repository::open(path)?;
+    let remote_name = template.remote_name();
+
+    match previous {
+        Some(url) if url == template.git_path => {}
+        Some(url) => repo.remote_set_url(remote_name, &url)?,
+        None => repo.remote_delete(remote_name)?,
+    }
+
+    Ok(())
+}
+
+/// Remove symlinks of template, that point into its old path
+fn remove_old_links(template: &Template, old_path: &Path) -> Result<(), DmanError> {
+    for file in &template.files {
+        let target = expand_home(&file.target);
+        if fs::read_link(&target).is_ok_and(|link| link == old_path.join(&file.source)) {
+            fs::remove_file(&target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Change fields of registered template
+///
+/// Template goes through the same checks as `dman new`
+/// When name changes, template file is renamed, when git_path or remote changes,
+/// remote of repository is pointed to git_path
+/// When path changes, checkout is moved there if `move_checkout` is set or user agrees,
+/// otherwise repository is expected there or cloned into it, symlinks of template
+/// are then pointed to the new path
+///
+/// # Errors
+///
+/// * If template doesn't exist, or there's a template with the new name
+/// * If any change is invalid
+/// * If template with changes doesn't pass checks of `dman new`
+pub fn edit_template(name: String, changes: Changes, move_checkout: bool) -> Result<(), DmanError> {
+    let template_folder = set_folders()?;
+    let old = match_user_input_with_existing_templates(Some(name), None, None)?;
+
+    let mut template = old.clone();
+    changes.apply(&mut template)?;
+
+    let old_path = expand_home(&old.path);
+    let path = expand_home(&template.path);
+    let moved = path != old_path;
+    // Path is kept as it was written, unless it points elsewhere
+    if moved {
+        template.path = path.to_string_lossy().to_string();
+    } else {
+        template.path = old.path.clone();
+    }
+
+    if template_to_toml(&template)? == template_to_toml(&old)? {
+        println!("Nothing to change");
+        return Ok(());
+    }
+
+    let renamed = template.name != old.name;
+    if renamed {
+        if template.name.is_empty() || template.name.contains(['/', '\\']) {
+            return Err(DmanError::InvalidInput(
+                "Name can't be empty or contain slashes".to_string(),
+            ));
+        }
+        if Path::new(&template_folder)
+            .join(format!("{}.toml", template.name))
+            .exists()
+        {
+            return Err(DmanError::AlreadyExists(format!(
+                "Template {:?}",
+                template.name
+            )));
+        }
+    }
+
+    // Nothing is touched, until new git path is known to exist
+    if template.git_path != old.git_path {
+        check_if_remote_exists(template.git_path.clone())?;
+    }
+
+    let moving = moved
+        && old_path.is_dir()
+        && is_free(&path)?
+        && (move_checkout
+            || Question::new(&format!(
+                "Move checkout from {old_path:?} to {path:?}? Otherwise it's cloned"
+            ))
+            .yes_no()
+            .show_defaults()
+            .until_acceptable()
+            .ask()
+                == Some(Answer::YES));
+
+    // Checkout is checked before it's moved, so failed check leaves it where it was
+    let checkout = if moving { &old_path } else { &path };
+    if !moving {
+        clone_if_missing(&template, checkout)?;
+    }
+    // Remote is changed for the check, failed check points it back
+    let previous = update_remote(&template, checkout)?;
+    if let Err(e) = check_template(&template, checkout) {
+        restore_remote(&template, checkout, previous)?;
+        return Err(e);
+    }
+
+    if moving {
+        move_repository(&old_path, &path)?;
+    }
+
+    save_template(&template)?;
+    if renamed {
+        fs::remove_file(Path::new(&template_folder).join(format!("{}.toml", old.name)))?;
+    }
+
+    // Deployed copies are tracked by name of template and path of their sources
+    if renamed || moved {
+        let mut state = DeployState::load()?;
+        state.rename(&old.name, &template.name);
+        state.move_sources(&template.name, &old_path, &path);
+        state.save()?;
+    }
+
+    println!("Template {:?} updated", template.name);
+
+    // Symlinks point into the old checkout
+    if moved && template.deploy == DeployMode::Symlink {
+        remove_old_links(&template, &old_path)?;
+        link(Some(template.name.clone()), None, None, None)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{fmt, io};
+
+/// Errors returned by dotfile-manager
+#[derive(Debug)]
+pub enum DmanError {
+    /// Template, file or path wasn't found
+    NotFound(String),
+    /// User input matches more than one template
+    AmbiguousMatch(Vec<String>),
+    /// Template, file or path already exists
+    AlreadyExists(String),
+    /// User input or template contents are not valid
+    InvalidInput(String),
+    /// Reading or writing filesystem failed
+    Io(io::Error),
+    /// Template file couldn't be parsed
+    TomlParse {
+        file: String,
+        source: toml::de::Error,
+    },
+    /// Git operation failed
+    Git(git2::Error),
+    /// Pull stopped on merge conflicts, that have to be resolved by user
+    MergeConflict {
+        template: String,
+        files: Vec<String>,
+    },
+    /// Some of templates pulled together failed or have conflicts
+    PullFailed(Vec<String>),
+    /// Templated file couldn't be rendered
+    Render { file: String, source: tera::Error },
+    /// Secret couldn't be encrypted or decrypted
+    Secret(String),
+    /// Hook command of template failed
+    Hook {
+        template: String,
+        hook: String,
+        message: String,
+    },
+    /// Operation needs network, but dman is offline
+    Offline(String),
+}
+
+impl DmanError {
+    /// Exit code the CLI should return for this error
+    ///
+    /// * 2 - invalid input
+    /// * 3 - not found
+    /// * 4 - ambiguous match
+    /// * 5 - already exists
+    /// * 6 - IO error
+    /// * 7 - TOML parse error
+    /// * 8 - Git error
+    /// * 9 - merge conflict
+    /// * 10 - some templates couldn't be pulled
+    /// * 11 - templated file couldn't be rendered
+    /// * 12 - secret couldn't be encrypted or decrypted
+    /// * 13 - hook failed
+    /// * 14 - operation needs network, but dman is offline
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            DmanError::InvalidInput(_) => 2,
+            DmanError::NotFound(_) => 3,
+            DmanError::AmbiguousMatch(_) => 4,
+            DmanError::AlreadyExists(_) => 5,
+            DmanError::Io(_) => 6,
+            DmanError::TomlParse { .. } => 7,
+            DmanError::Git(_) => 8,
+            DmanError::MergeConflict { .. } => 9,
+            DmanError::PullFailed(_) => 10,
+            DmanError::Render { .. } => 11,
+            DmanError::Secret(_) => 12,
+            DmanError::Hook { .. } => 13,
+            DmanError::Offline(_) => 14,
+        }
+    }
+}
+
+impl fmt::Display for DmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DmanError::NotFound(what) => write!(f, "{what} not found"),
+            DmanError::AmbiguousMatch(names) => write!(
+                f,
+                "Input matches multiple templates: {}, be more specific",
+                names.join(", ")
+            ),
+            DmanError::AlreadyExists(what) => write!(f, "{what} already exists"),
+            DmanError::InvalidInput(message) | DmanError::Secret(message) => write!(f, "{message}"),
+            DmanError::Io(error) => write!(f, "IO error: {error}"),
+            DmanError::TomlParse { file, source } => write!(f, "Couldn't parse {file}: {source}"),
+            DmanError::Git(error) => write!(f, "Git error: {}", error.message()),
+            DmanError::MergeConflict { template, files } => write!(
+                f,
+                "Merge conflicts in {template}: {}, resolve them with `dman conflicts {template}`",
+                files.join(", ")
+            ),
+            DmanError::Render { file, source } => {
+                // Tera keeps the useful part of the message in nested errors
+                write!(f, "Couldn't render {file}: {source}")?;
+                let mut cause = std::error::Error::source(source);
+                while let Some(error) = cause {
+                    write!(f, ": {error}")?;
+                    cause = error.source();
+                }
+                Ok(())
+            }
+            DmanError::Hook {
+                template,
+                hook,
+                message,
+            } => write!(f, "{hook} hook of {template} failed: {message}"),
+            DmanError::Offline(what) => write!(
+                f,
+                "{what} needs network, run it without --offline and `offline` in config.toml"
+            ),
+            DmanError::PullFailed(names) => {
+                write!(
+                    f,
+                    "Couldn't pull {} template(s): {}",
+                    names.len(),
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DmanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DmanError::Io(error) => Some(error),
+            DmanError::TomlParse { source, .. } => Some(source),
+            DmanError::Git(error) => Some(error),
+            DmanError::Render { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DmanError {
+    fn from(error: io::Error) -> Self {
+        DmanError::Io(error)
+    }
+}
+
+impl From<git2::Error> for DmanError {
+    fn from(error: git2::Error) -> Self {
+        DmanError::Git(error)
+    }
+}
@@ -1,44 +1,146 @@
-use crate::lib::{get_existing_templates, process_template_to_struct};
+use crate::lib::{get_templates_to_vec, DmanError, Template};
+use glob::Pattern;
 use mytools::{env::get_home_folder, question_yes_no};
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
 
-pub fn export_templates(export_file: String) {
-    // Get all templates
-    let templates = get_existing_templates();
-    let home_folder = get_home_folder();
+/// Format of exported templates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
 
-    let mut toml: Vec<String> = Vec::new();
-    for template in templates {
-        let mut template = process_template_to_struct(&template);
+impl Format {
+    /// Get format from extension of file, e.g. templates.json
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
 
-        // Replace home directory with ~ so it is portable
-        template.path = template.path.replace(&home_folder, "~");
+impl FromStr for Format {
+    type Err = DmanError;
 
-        toml.push(format!(
-            "[{}]\nname = \"{}\"\npath = \"{}\"\ngit_path = \"{}\"\n\n",
-            template.name, template.name, template.path, template.git_path
-        ));
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(DmanError::InvalidInput(format!("Unknown format: {s}"))),
+        }
     }
+}
 
-    // Sort templates by name alphabetically
-    toml.sort();
+/// Which templates are exported, every template if it's empty
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// Globs of template names, e.g. nvim-*
+    pub names: Vec<String>,
+    pub tags: Vec<String>,
+}
 
-    // Covert to string
-    // fs::write doesn't work with Vec<String>
-    let toml: String = toml.join("");
+impl Filter {
+    /// Template has to match one of the names and one of the tags, if they're set
+    fn matches(&self, template: &Template, names: &[Pattern]) -> bool {
+        (names.is_empty() || names.iter().any(|name| name.matches(&template.name)))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| template.tags.contains(tag)))
+    }
+}
+
+/// Replace home folder with ~ so exported path works on other machines
+fn portable(path: &str, home_folder: &str) -> String {
+    match path.strip_prefix(home_folder) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+        _ => path.to_string(),
+    }
+}
 
-    // Remove last newline
-    // toml.pop();
+fn serialize(templates: &BTreeMap<String, Template>, format: Format) -> Result<String, DmanError> {
+    let serialized = match format {
+        Format::Toml => toml::to_string(templates).map_err(|e| e.to_string()),
+        Format::Json => serde_json::to_string_pretty(templates)
+            .map(|json| json + "\n")
+            .map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(templates).map_err(|e| e.to_string()),
+    };
+
+    serialized.map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize templates: {e}")))
+}
+
+/// Export templates with all their settings, sorted by name, so they can be imported
+/// by `dman import`
+///
+/// # Arguments
+///
+/// * `export_file` - File templates are written to, they're printed to stdout if it's not set
+/// * `format` - Format of export, taken from extension of file or toml if it's not set
+/// * `filter` - Which templates are exported
+///
+/// # Errors
+///
+/// * If name glob is invalid
+/// * If no template matches the filter
+/// * If templates can't be serialized or written
+pub fn export_templates(
+    export_file: Option<String>,
+    format: Option<Format>,
+    filter: &Filter,
+) -> Result<(), DmanError> {
+    let names = filter
+        .names
+        .iter()
+        .map(|name| {
+            Pattern::new(name)
+                .map_err(|e| DmanError::InvalidInput(format!("Invalid name glob {name:?}: {e}")))
+        })
+        .collect::<Result<Vec<Pattern>, DmanError>>()?;
+
+    let home_folder = get_home_folder();
+    let mut templates = BTreeMap::new();
+    for mut template in get_templates_to_vec()? {
+        if !filter.matches(&template, &names) {
+            continue;
+        }
+
+        template.path = portable(&template.path, &home_folder);
+        for file in &mut template.files {
+            file.target = portable(&file.target, &home_folder);
+        }
+        templates.insert(template.name.clone(), template);
+    }
+
+    if templates.is_empty() {
+        return Err(DmanError::NotFound("Templates to export".to_string()));
+    }
+
+    let Some(export_file) = export_file else {
+        print!("{}", serialize(&templates, format.unwrap_or(Format::Toml))?);
+        return Ok(());
+    };
 
-    // Check if file already exists
     let export_file_path = Path::new(&export_file);
+    let format = format
+        .or_else(|| Format::from_extension(export_file_path))
+        .unwrap_or(Format::Toml);
+    let contents = serialize(&templates, format)?;
+
+    // Check if file already exists
     if export_file_path.exists() {
         println!("File already exists");
         question_yes_no!("Do you want to overwrite it?");
     }
 
-    // Write to file
-    fs::write(&export_file, toml).unwrap();
+    fs::write(export_file_path, contents)?;
+
+    println!(
+        "Exported {} template(s) to \"{export_file}\"",
+        templates.len()
+    );
 
-    println!("Exported templates to \"{export_file}\"");
+    Ok(())
 }
@@ -3,7 +3,7 @@
 #[path = "../../lib.rs"]
 mod lib;
 
-use lib::get_templates_to_vec;
+use lib::{get_existing_templates, machine::Machine, process_template_to_struct, DmanError};
 
 fn main() {
     tauri::Builder::default()
@@ -13,9 +13,21 @@ fn main() {
 }
 
 /// Create a list of templates from the Templates folder
+///
+/// Templates that can't be parsed are skipped, so one broken file doesn't hide the rest
+/// Templates for other machines are skipped too
 #[tauri::command]
-fn reload_templates() -> Vec<[String; 3]> {
-    let mut templates = get_templates_to_vec();
+fn reload_templates() -> Result<Vec<[String; 3]>, String> {
+    let machine = Machine::current().map_err(|e| e.to_string())?;
+    let mut templates = Vec::new();
+    for template_file in get_existing_templates().map_err(|e: DmanError| e.to_string())? {
+        match process_template_to_struct(&template_file) {
+            Ok(template) if template.applies_to(&machine) => templates.push(template),
+            Ok(_) => {}
+            Err(e) => eprintln!("Skipping template: {e}"),
+        }
+    }
+
     let mut result: Vec<[String; 3]> = Vec::new();
     // Sort templates by name alphabetically
     templates.sort_by(|a, b| a.name.cmp(&b.name));
@@ -23,5 +35,5 @@ fn reload_templates() -> Vec<[String; 3]> {
         result.push([template.name, template.path, template.git_path]);
     }
 
-    result
+    Ok(result)
 }
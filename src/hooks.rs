@@ -0,0 +1,226 @@
+use crate::lib;
+use git2::Repository;
+use lib::{expand_home, match_user_input_with_existing_templates, DmanError, Template};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+/// Moment of syncing, when command of template is run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    PreClone,
+    PostClone,
+    PrePull,
+    PostPull,
+    PrePush,
+    PostPush,
+}
+
+impl Hook {
+    /// Command of this hook set in template
+    fn command(self, template: &Template) -> Option<&str> {
+        match self {
+            Hook::PreClone => template.pre_clone.as_deref(),
+            Hook::PostClone => template.post_clone.as_deref(),
+            Hook::PrePull => template.pre_pull.as_deref(),
+            Hook::PostPull => template.post_pull.as_deref(),
+            Hook::PrePush => template.pre_push.as_deref(),
+            Hook::PostPush => template.post_push.as_deref(),
+        }
+    }
+
+    /// Command of this hook in template, to change it
+    pub fn command_mut(self, template: &mut Template) -> &mut Option<String> {
+        match self {
+            Hook::PreClone => &mut template.pre_clone,
+            Hook::PostClone => &mut template.post_clone,
+            Hook::PrePull => &mut template.pre_pull,
+            Hook::PostPull => &mut template.post_pull,
+            Hook::PrePush => &mut template.pre_push,
+            Hook::PostPush => &mut template.post_push,
+        }
+    }
+}
+
+impl FromStr for Hook {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pre_clone" => Ok(Hook::PreClone),
+            "post_clone" => Ok(Hook::PostClone),
+            "pre_pull" => Ok(Hook::PrePull),
+            "post_pull" => Ok(Hook::PostPull),
+            "pre_push" => Ok(Hook::PrePush),
+            "post_push" => Ok(Hook::PostPush),
+            _ => Err(DmanError::InvalidInput(format!("Unknown hook: {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hook::PreClone => write!(f, "pre_clone"),
+            Hook::PostClone => write!(f, "post_clone"),
+            Hook::PrePull => write!(f, "pre_pull"),
+            Hook::PostPull => write!(f, "post_pull"),
+            Hook::PrePush => write!(f, "pre_push"),
+            Hook::PostPush => write!(f, "post_push"),
+        }
+    }
+}
+
+/// Commits of HEAD before and after syncing, passed to hooks
+#[derive(Clone, Debug, Default)]
+pub struct Commits {
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Get commit HEAD of repository points to, None if there's no repository or commit yet
+pub fn head_commit(path: &str) -> Option<String> {
+    let repo = Repository::open(expand_home(path)).ok()?;
+    let commit = repo.head().ok()?.target()?;
+
+    Some(commit.to_string())
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+
+    shell
+}
+
+/// Run hook of template in shell, if template has one
+///
+/// Hook runs in template path, pre_clone in its parent folder, with environment variables:
+///
+/// * `DMAN_TEMPLATE` - name of the template
+/// * `DMAN_TEMPLATE_PATH` - path of the template
+/// * `DMAN_HOOK` - name of the hook, e.g. post_pull
+/// * `DMAN_OLD_COMMIT`, `DMAN_NEW_COMMIT` - HEAD before and after syncing,
+///   empty if there was no commit
+///
+/// With `quiet`, output of hook is printed only if it fails
+///
+/// # Errors
+///
+/// * If template path doesn't exist, except for pre_clone hook
+/// * If hook can't be started or exits with failure
+pub fn run_hook(
+    template: &Template,
+    hook: Hook,
+    commits: &Commits,
+    quiet: bool,
+) -> Result<(), DmanError> {
+    let Some(command) = hook.command(template) else {
+        return Ok(());
+    };
+
+    let path = expand_home(&template.path);
+    let cwd = match hook {
+        // Template isn't cloned yet, so its parent folder may not exist either
+        Hook::PreClone => {
+            let parent = path.parent().unwrap_or(&path).to_path_buf();
+            fs::create_dir_all(&parent)?;
+            parent
+        }
+        _ if !path.is_dir() => {
+            return Err(DmanError::NotFound(format!(
+                "Path {path:?} of template {}",
+                template.name
+            )))
+        }
+        _ => path.clone(),
+    };
+
+    let mut shell = shell(command);
+    shell
+        .current_dir(&cwd)
+        .env("DMAN_TEMPLATE", &template.name)
+        .env("DMAN_TEMPLATE_PATH", &path)
+        .env("DMAN_HOOK", hook.to_string())
+        .env(
+            "DMAN_OLD_COMMIT",
+            commits.old.as_deref().unwrap_or_default(),
+        )
+        .env(
+            "DMAN_NEW_COMMIT",
+            commits.new.as_deref().unwrap_or_default(),
+        );
+
+    let failed = |message: String| DmanError::Hook {
+        template: template.name.clone(),
+        hook: hook.to_string(),
+        message,
+    };
+
+    if quiet {
+        let output = shell
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| failed(e.to_string()))?;
+
+        if !output.status.success() {
+            println!("Output of {hook} hook of {}:", template.name);
+            io::stdout().write_all(&output.stdout)?;
+            io::stderr().write_all(&output.stderr)?;
+
+            return Err(failed(output.status.to_string()));
+        }
+    } else {
+        println!("Running {hook} hook of {}: {command}", template.name);
+        let status = shell.status().map_err(|e| failed(e.to_string()))?;
+
+        if !status.success() {
+            return Err(failed(status.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Run hook of template by hand, outside of syncing
+///
+/// Both commits passed to hook are the current HEAD
+///
+/// # Errors
+///
+/// * If template doesn't have the hook
+/// * If hook fails
+pub fn run_hook_by_hand(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    hook: Hook,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+
+    if hook.command(&template).is_none() {
+        return Err(DmanError::NotFound(format!(
+            "{hook} hook of {}",
+            template.name
+        )));
+    }
+
+    let head = head_commit(&template.path);
+    let commits = Commits {
+        old: head.clone(),
+        new: head,
+    };
+
+    run_hook(&template, hook, &commits, false)
+}
@@ -1,182 +1,779 @@
+#[path = "./import/chezmoi.rs"]
+mod chezmoi;
+#[path = "./import/dotbot.rs"]
+mod dotbot;
+#[path = "./import/stow.rs"]
+mod stow;
+#[path = "./import/yadm.rs"]
+mod yadm;
+
 use crate::args;
 use crate::lib;
-use args::create_template;
-use lib::{get_existing_templates, process_template_to_struct, Template};
-use mytools::pretty_panic;
-use std::path::Path;
+use args::{
+    create::{check_template, clone_template},
+    export::Format,
+};
+use git2::Repository;
+use lib::{
+    expand_home, get_templates_to_vec, save_template, set_folders, DmanError, Template,
+    DEFAULT_REMOTE,
+};
+use mytools::{env::get_home_folder, warn};
+use owo_colors::OwoColorize;
+use question::{Answer, Question};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Where templates are imported from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Toml, json or yaml file written by `dman export`
+    Dman,
+    /// GNU Stow folder with packages
+    Stow,
+    /// dotbot repository with install.conf.yaml
+    Dotbot,
+    /// Bare repository of yadm
+    Yadm,
+    /// Source folder of chezmoi
+    Chezmoi,
+}
+
+impl FromStr for Source {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dman" => Ok(Source::Dman),
+            "stow" => Ok(Source::Stow),
+            "dotbot" => Ok(Source::Dotbot),
+            "yadm" => Ok(Source::Yadm),
+            "chezmoi" => Ok(Source::Chezmoi),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown import source: {s}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Dman => write!(f, "dman"),
+            Source::Stow => write!(f, "stow"),
+            Source::Dotbot => write!(f, "dotbot"),
+            Source::Yadm => write!(f, "yadm"),
+            Source::Chezmoi => write!(f, "chezmoi"),
+        }
+    }
+}
+
+/// What happens with imported template, that has the same name as existing one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Existing template is kept
+    #[default]
+    Skip,
+    /// Existing template is replaced
+    Overwrite,
+    /// Imported template gets free name, e.g. nvim-2
+    Rename,
+    /// User is asked for every conflict
+    Ask,
+}
+
+impl FromStr for OnConflict {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" | "s" => Ok(OnConflict::Skip),
+            "overwrite" | "o" => Ok(OnConflict::Overwrite),
+            "rename" | "r" => Ok(OnConflict::Rename),
+            "ask" => Ok(OnConflict::Ask),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown action {s:?}, use skip, overwrite, rename or ask"
+            ))),
+        }
+    }
+}
+
+/// How templates are imported
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportOptions {
+    pub on_conflict: OnConflict,
+    /// Only print what would be imported
+    pub dry_run: bool,
+}
+
+/// What import does with template
+#[derive(Debug)]
+enum Action {
+    /// Template with new name is added
+    Add,
+    /// Template is added under new name, holds the original name
+    Rename(String),
+    /// Existing template is replaced, holds the existing template
+    Overwrite(Box<Template>),
+    /// User will be asked, only in dry run
+    Ask(Box<Template>),
+    /// Existing template is the same
+    Unchanged,
+    /// Existing template differs and is kept
+    Skip,
+}
+
+/// Template with what import does with it
+#[derive(Debug)]
+struct Planned {
+    template: Template,
+    action: Action,
+    /// Path doesn't exist yet, so repository is cloned
+    clone: bool,
+}
+
+impl Planned {
+    fn is_written(&self) -> bool {
+        matches!(
+            self.action,
+            Action::Add | Action::Rename(_) | Action::Overwrite(_)
+        )
+    }
+}
+
+/// Templates translated from setup of another dotfile manager
+#[derive(Debug, Default)]
+pub struct Translation {
+    pub templates: Vec<Template>,
+    /// Parts of setup, that dman can't express, shown to user after import
+    pub untranslated: Vec<String>,
+}
+
+/// Get URL of origin of repository, which path is in
+fn origin_url(path: &Path) -> Option<String> {
+    let repo = Repository::discover(path).ok()?;
+    let remote = repo.find_remote(DEFAULT_REMOTE).ok()?;
+
+    remote.url().map(str::to_string)
+}
+
+/// Name of template made from folder, leading '.' is trimmed, like in `dman adopt`
+fn folder_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+        .unwrap_or_else(|| "dotfiles".to_string())
+}
+
+/// Target of deployed file, written with `~` if it's inside home folder
+fn target_path(folder: &Path, relative: &Path) -> String {
+    let target = folder.join(relative);
+
+    match target.strip_prefix(get_home_folder()) {
+        Ok(inside_home) => format!("~/{}", inside_home.to_string_lossy()),
+        Err(_) => target.to_string_lossy().to_string(),
+    }
+}
+
+/// Empty folder in temporary folder for tests of translators
+#[cfg(test)]
+fn test_folder(name: &str) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!("dman-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+
+    folder
+}
+
+/// Guess which dotfile manager path belongs to
+fn detect_source(path: &Path) -> Result<Source, DmanError> {
+    if path.is_file() {
+        return match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(Source::Dman),
+            // Config of dotbot is a list of directives, export is a table of templates
+            Some("yaml" | "yml" | "json") if is_exported(path) => Ok(Source::Dman),
+            Some("yaml" | "yml" | "json") => Ok(Source::Dotbot),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Couldn't detect what {path:?} is, set it with --from"
+            ))),
+        };
+    }
+
+    if dotbot::config_file(path).is_some() {
+        return Ok(Source::Dotbot);
+    }
+
+    if Repository::open(path).is_ok_and(|repo| repo.is_bare()) {
+        return Ok(Source::Yadm);
+    }
+
+    let is_chezmoi = path.read_dir()?.flatten().any(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        name.starts_with("dot_") || name.starts_with(".chezmoi")
+    });
+    if is_chezmoi {
+        return Ok(Source::Chezmoi);
+    }
 
-/// Import templates from a file
+    Ok(Source::Stow)
+}
+
+/// Import templates from a file or from setup of another dotfile manager
 ///
 /// # Arguments
 ///
-/// * `file_path` - Path to the file
+/// * `file_path` - Path to toml, json or yaml file exported by dman, GNU Stow folder, dotbot config
+///   or its repository, bare repository of yadm or source folder of chezmoi
+/// * `from` - Where templates are imported from, detected from path if it's not set
+/// * `options` - What happens with templates, that already exist, and if it's only dry run
+///
+/// Templates of other dotfile managers are deployed as symlinks, path and git_path
+/// are inferred from their repository
+/// Everything that couldn't be translated is listed after import
+///
+/// Import is transactional, every template is checked first and if any of them is invalid,
+/// nothing is cloned or written
+/// Then missing repositories are cloned and templates go through checks of `dman new`,
+/// if anything fails, cloned repositories are removed and template files are restored
+/// Dry run checks templates only before cloning, so remotes aren't checked
 ///
-/// # Panics
+/// # Errors
 ///
 /// * If the file does not exist
-/// * If the file is a directory
-/// * If the file is not a toml file
-/// * If the file does not contain a name
-/// * If the file does not contain a path
-/// * If the file does not contain a git_path
+/// * If source can't be detected or read
+/// * If exported file doesn't contain name, path or git_path of template
+/// * If any template is invalid or can't be cloned
 ///
 /// # Examples
 ///
 /// ```
 /// use dotfile_manager::import_templates;
 ///
-/// import_templates(String::from("/home/user/.config/dotfile-manager/templates.toml"));
+/// import_templates(
+///     String::from("/home/user/.config/dotfile-manager/templates.toml"),
+///     None,
+///     ImportOptions::default(),
+/// )?;
 /// ```
-pub fn import_templates(file_path: String) {
+pub fn import_templates(
+    file_path: String,
+    from: Option<Source>,
+    options: ImportOptions,
+) -> Result<(), DmanError> {
     let file_path = Path::new(&file_path);
 
     // check if file exists
     if !file_path.exists() {
-        pretty_panic!("File does not exist");
+        return Err(DmanError::NotFound(format!("File {file_path:?}")));
     }
 
-    // check if file is not directory
-    if file_path.is_dir() {
-        pretty_panic!("File is directory and not file");
+    let source = match from {
+        Some(source) => source,
+        None => detect_source(file_path)?,
+    };
+
+    let path = file_path.canonicalize()?;
+    let translation = match source {
+        Source::Dman => Translation {
+            templates: read_exported(file_path)?,
+            untranslated: Vec::new(),
+        },
+        Source::Stow => stow::translate(&path)?,
+        Source::Dotbot => dotbot::translate(&path)?,
+        Source::Yadm => yadm::translate(&path)?,
+        Source::Chezmoi => chezmoi::translate(&path)?,
+    };
+
+    let plan = plan_import(translation.templates, options)?;
+    let problems: Vec<String> = plan
+        .iter()
+        .filter(|planned| planned.is_written() || matches!(planned.action, Action::Ask(_)))
+        .filter_map(|planned| {
+            precheck(&planned.template)
+                .err()
+                .map(|reason| format!("{}: {reason}", planned.template.name))
+        })
+        .collect();
+
+    if options.dry_run {
+        print_plan(&plan);
     }
 
-    // check if file is a toml file
-    match file_path.extension() {
-        Some(extension) => match extension.to_str() {
-            Some(extension) => {
-                if extension != "toml" {
-                    pretty_panic!("File is not a toml file");
-                }
+    if !problems.is_empty() {
+        for problem in &problems {
+            warn!("{problem}");
+        }
+        return Err(DmanError::InvalidInput(format!(
+            "{} template(s) are invalid, nothing was imported",
+            problems.len()
+        )));
+    }
+
+    if !options.dry_run {
+        println!("Importing templates from {source}...");
+        apply_plan(plan)?;
+    }
+
+    if !translation.untranslated.is_empty() {
+        println!("{}", "Couldn't translate:".yellow());
+        for item in &translation.untranslated {
+            println!("  - {item}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Template with paths expanded, the way it's written, so templates can be compared
+fn normalized(template: &Template) -> Template {
+    let mut template = template.clone();
+    template.path = expand_home(&template.path).to_string_lossy().to_string();
+    for file in &mut template.files {
+        file.target = expand_home(&file.target).to_string_lossy().to_string();
+    }
+
+    template
+}
+
+fn to_table(template: &Template) -> toml::value::Table {
+    match toml::Value::try_from(template) {
+        Ok(toml::Value::Table(table)) => table,
+        _ => toml::value::Table::new(),
+    }
+}
+
+/// Settings, that differ between existing and imported template, e.g. `path: "a" -> "b"`
+fn changed_fields(old: &Template, new: &Template) -> Vec<String> {
+    let (old, new) = (to_table(old), to_table(new));
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let show = |value: Option<&toml::Value>| match value {
+        Some(value) => value.to_string(),
+        None => "unset".to_string(),
+    };
+
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| format!("{key}: {} -> {}", show(old.get(key)), show(new.get(key))))
+        .collect()
+}
+
+/// Get name, that isn't used by any template, e.g. nvim-2
+fn free_name(name: &str, taken: &HashSet<String>) -> String {
+    (2..)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+/// Ask user what to do with template, that already exists, skip is the default
+fn ask_on_conflict(name: &str, changes: &[String]) -> OnConflict {
+    println!("Template {name:?} already exists and differs:");
+    for change in changes {
+        println!("    {change}");
+    }
+
+    let answer = Question::new("Skip, overwrite or rename it?")
+        .acceptable(vec!["skip", "overwrite", "rename", "s", "o", "r"])
+        .until_acceptable()
+        .default(Answer::RESPONSE("skip".to_string()))
+        .show_defaults()
+        .ask();
+
+    match answer {
+        Some(Answer::RESPONSE(action)) => action.parse().unwrap_or(OnConflict::Skip),
+        _ => OnConflict::Skip,
+    }
+}
+
+/// Decide what happens with every imported template
+fn plan_import(
+    templates: Vec<Template>,
+    options: ImportOptions,
+) -> Result<Vec<Planned>, DmanError> {
+    let template_folder = PathBuf::from(set_folders()?);
+    let existing: BTreeMap<String, Template> = get_templates_to_vec()?
+        .into_iter()
+        .map(|template| (template.name.clone(), template))
+        .collect();
+
+    // Renamed templates can't take name of existing or other imported template
+    let mut taken: HashSet<String> = existing.keys().cloned().collect();
+    taken.extend(templates.iter().map(|template| template.name.clone()));
+
+    let mut seen = HashSet::new();
+    let mut plan = Vec::new();
+
+    for template in templates {
+        if !seen.insert(template.name.clone()) {
+            return Err(DmanError::InvalidInput(format!(
+                "Template {:?} is imported twice",
+                template.name
+            )));
+        }
+
+        let mut template = normalized(&template);
+        let action = match existing.get(&template.name) {
+            // Broken template file isn't in existing templates, but it isn't overwritten
+            None if template_folder
+                .join(format!("{}.toml", template.name))
+                .exists() =>
+            {
+                return Err(DmanError::AlreadyExists(format!(
+                    "Unreadable template file {:?}",
+                    template_folder.join(format!("{}.toml", template.name))
+                )));
             }
-            None => {
-                pretty_panic!("File is not a toml file");
+            None => Action::Add,
+            Some(old) => {
+                let old = normalized(old);
+                let changes = changed_fields(&old, &template);
+
+                let on_conflict = match options.on_conflict {
+                    _ if changes.is_empty() => None,
+                    OnConflict::Ask if options.dry_run => {
+                        plan.push(Planned {
+                            template,
+                            action: Action::Ask(Box::new(old)),
+                            clone: false,
+                        });
+                        continue;
+                    }
+                    OnConflict::Ask => Some(ask_on_conflict(&old.name, &changes)),
+                    on_conflict => Some(on_conflict),
+                };
+
+                match on_conflict {
+                    None => Action::Unchanged,
+                    Some(OnConflict::Overwrite) => Action::Overwrite(Box::new(old)),
+                    Some(OnConflict::Rename) => {
+                        let name = free_name(&template.name, &taken);
+                        taken.insert(name.clone());
+                        Action::Rename(std::mem::replace(&mut template.name, name))
+                    }
+                    Some(_) => Action::Skip,
+                }
             }
-        },
-        None => {
-            pretty_panic!("File is not a toml file");
-        }
+        };
+
+        let path = Path::new(&template.path);
+        let clone = !path.exists() || path.read_dir().is_ok_and(|mut dir| dir.next().is_none());
+        plan.push(Planned {
+            template,
+            action,
+            clone,
+        });
     }
 
-    let file = std::fs::read_to_string(file_path).unwrap();
-    let file_contents: toml::Value = toml::from_str(&file).unwrap();
-    let mut templates: Vec<Template> = Vec::new();
+    Ok(plan)
+}
 
-    // put templates to vector
-    for (key, value) in file_contents.as_table().unwrap().iter() {
-        #[cfg(debug_assertions)]
-        {
-            println!("{key}: {value}");
+/// Check template before anything is cloned or written, checks of `dman new` run
+/// after cloning
+///
+/// Returns reason, why template can't be imported
+fn precheck(template: &Template) -> Result<(), String> {
+    if template.name.is_empty() || template.name.contains(['/', '\\']) {
+        return Err("name can't be empty or contain slashes".to_string());
+    }
+
+    let path = Path::new(&template.path);
+    if path.is_file() {
+        return Err(format!("path {path:?} is a file"));
+    }
+
+    let is_empty = path.read_dir().map_or(true, |mut dir| dir.next().is_none());
+    if is_empty {
+        if template.git_path.is_empty() {
+            return Err(format!(
+                "path {path:?} doesn't exist and there's no git_path to clone it from"
+            ));
         }
+        return Ok(());
+    }
 
-        // check if file contains table name
-        // otherwise it would use wrong key as table name and couldn't find the values
+    match Repository::open(path) {
+        // Translated folders without Git are registered as they are
+        Err(_) if template.git_path.is_empty() => Ok(()),
+        Err(_) => Err(format!("path {path:?} isn't a Git repository")),
+        Ok(repo) => {
+            let remote_name = template.remote_name();
+            if !template.git_path.is_empty() && repo.find_remote(remote_name).is_err() {
+                return Err(format!("{path:?} has no remote {remote_name}"));
+            }
+            Ok(())
+        }
+    }
+}
 
-        match key.as_str() {
-            "name" | "path" | "git_path" => {
-                pretty_panic!("Table name missing");
+/// Print what import would do, like diff
+fn print_plan(plan: &[Planned]) {
+    for planned in plan {
+        let name = &planned.template.name;
+
+        match &planned.action {
+            Action::Add => println!("{} {name}", "+".green()),
+            Action::Rename(original) => {
+                println!("{} {name} (renamed from {original})", "+".green())
             }
-            _ => {}
+            Action::Overwrite(old) | Action::Ask(old) => {
+                let suffix = match planned.action {
+                    Action::Ask(_) => " (asks what to do)",
+                    _ => "",
+                };
+                println!("{} {name}{suffix}", "~".yellow());
+                for change in changed_fields(old, &planned.template) {
+                    println!("    {change}");
+                }
+            }
+            Action::Unchanged => println!("{}", format!("= {name}").dimmed()),
+            Action::Skip => println!(
+                "{}",
+                format!("  {name} skipped, existing template differs").dimmed()
+            ),
         }
 
-        // check if file contains valid values
-        // if not, panic
-        // if yes, put them in a vector
-        // and then add them to the templates vector
+        if planned.clone && planned.is_written() {
+            println!(
+                "    clone {} into {:?}",
+                planned.template.git_path, planned.template.path
+            );
+        }
+    }
+}
+
+/// Changes made by import, so they can be undone if import fails
+#[derive(Debug, Default)]
+struct Undo {
+    /// Cloned paths and whether they existed as empty folders before
+    cloned: Vec<(PathBuf, bool)>,
+    /// Written templates with templates they replaced
+    saved: Vec<(String, Option<Template>)>,
+}
 
-        let name = match value.get("name") {
-            Some(name) => name,
-            None => {
-                pretty_panic!("Template does not contain a name");
+impl Undo {
+    /// Put everything back, errors are only reported, so the rest is still undone
+    fn rollback(self) {
+        let template_folder = match set_folders() {
+            Ok(folder) => PathBuf::from(folder),
+            Err(e) => {
+                warn!("Couldn't restore templates: {e}");
+                return;
             }
         };
 
-        let path = match value.get("path") {
-            Some(path) => path,
-            None => {
-                pretty_panic!("Template does not contain a path");
+        for (name, old) in self.saved.into_iter().rev() {
+            let result = match old {
+                Some(old) => save_template(&old),
+                None => fs::remove_file(template_folder.join(format!("{name}.toml")))
+                    .map_err(DmanError::from),
+            };
+            if let Err(e) = result {
+                warn!("Couldn't restore template {name:?}: {e}");
             }
-        };
+        }
 
-        let git_path = match value.get("git_path") {
-            Some(git_path) => git_path,
-            None => {
-                pretty_panic!("Template does not contain a git_path");
+        for (path, existed) in self.cloned.into_iter().rev() {
+            let mut result = fs::remove_dir_all(&path);
+            if existed {
+                result = result.and_then(|_| fs::create_dir(&path));
             }
-        };
+            if let Err(e) = result {
+                warn!("Couldn't remove clone {path:?}: {e}");
+            }
+        }
+    }
+}
 
-        #[cfg(debug_assertions)]
-        {
-            println!("name: {name}");
-            println!("path: {path}");
-            println!("git_path: {git_path}");
-        }
-
-        // this is needed, because the values are wrapped in quotes
-        // and we need to remove them
-        // they look like this: "value"
-        // and we need to remove the quotes
-        // so they look like this: value
-        // and we can use them
-        // this is a workaround, because I don't know how to do it better
-        // if you know how to do it better, please open an issue on GitHub
-        // or make a pull request
-
-        let mut name = name.to_string();
-        name.pop();
-        name.remove(0);
-
-        let mut path = path.to_string();
-        path.pop();
-        path.remove(0);
-
-        let mut git_path = git_path.to_string();
-        git_path.pop();
-        git_path.remove(0);
-
-        let template = Template {
-            name,
-            path,
-            git_path,
+/// Clone missing repositories, check templates like `dman new` does and write them
+fn write_plan(plan: &[Planned], undo: &mut Undo) -> Result<(), DmanError> {
+    // More templates can share repository, e.g. packages of Stow
+    let mut cloned = HashSet::new();
+    for planned in plan.iter().filter(|planned| planned.clone) {
+        let template = &planned.template;
+        if !cloned.insert(template.path.clone()) {
+            continue;
+        }
+
+        let path = Path::new(&template.path);
+        undo.cloned.push((path.to_path_buf(), path.exists()));
+        fs::create_dir_all(path)?;
+        clone_template(template, path).map_err(|e| {
+            DmanError::InvalidInput(format!("Couldn't clone {}: {e}", template.name))
+        })?;
+    }
+
+    // Translated folders without Git are registered as they are
+    for template in plan
+        .iter()
+        .map(|planned| &planned.template)
+        .filter(|template| !template.git_path.is_empty())
+    {
+        check_template(template, Path::new(&template.path))
+            .map_err(|e| DmanError::InvalidInput(format!("{}: {e}", template.name)))?;
+    }
+
+    for planned in plan {
+        let old = match &planned.action {
+            Action::Overwrite(old) => Some(old.as_ref().clone()),
+            _ => None,
         };
+        save_template(&planned.template)?;
+        undo.saved.push((planned.template.name.clone(), old));
+    }
 
-        // check if template(s) already exists
-        let existing_templates = get_existing_templates();
-        let mut is_template_already_existing = false;
-        for existing_template in existing_templates {
-            let existing_template = process_template_to_struct(&existing_template);
+    Ok(())
+}
 
-            #[cfg(debug_assertions)]
-            {
-                println!("{:?}", existing_template.name);
-                println!("{:?}", template.name);
+/// Clone missing repositories, check and write templates, everything is undone
+/// if any step fails
+fn apply_plan(plan: Vec<Planned>) -> Result<(), DmanError> {
+    let plan: Vec<Planned> = plan
+        .into_iter()
+        .filter(|planned| {
+            let name = &planned.template.name;
+            match planned.action {
+                Action::Unchanged => println!("Template {name:?} is up to date"),
+                Action::Skip => println!("Template {name:?} already exists, skipping.."),
+                _ => return true,
             }
+            false
+        })
+        .collect();
+
+    let mut undo = Undo::default();
+    if let Err(e) = write_plan(&plan, &mut undo) {
+        warn!("{e}");
+        undo.rollback();
+        return Err(DmanError::InvalidInput(
+            "Import stopped, nothing was imported".to_string(),
+        ));
+    }
 
-            if existing_template.name == template.name {
-                // If template already exists, skip it
-                println!("Template {:?} already exists, skipping..", template.name);
-                is_template_already_existing = true;
+    for planned in plan {
+        let template = planned.template;
+
+        match planned.action {
+            Action::Rename(original) => {
+                println!("Template {original:?} imported as {:?}", template.name)
             }
+            Action::Overwrite(_) => println!("Template {:?} overwritten", template.name),
+            _ => println!("Template {:?} imported", template.name),
         }
 
-        if !(is_template_already_existing) {
-            templates.push(template);
+        if template.git_path.is_empty() {
+            println!(
+                "Template {:?} has no git_path, add remote {DEFAULT_REMOTE} to {:?} and set it",
+                template.name, template.path
+            );
         }
     }
 
-    #[cfg(debug_assertions)]
-    {
-        println!("templates: {templates:?}");
+    Ok(())
+}
+
+/// Parse file written by `dman export`, format is taken from its extension
+fn read_export_file(file_path: &Path) -> Result<toml::Value, DmanError> {
+    // check if file is not directory
+    if file_path.is_dir() {
+        return Err(DmanError::InvalidInput(format!(
+            "{file_path:?} is directory and not file"
+        )));
     }
 
-    println!("Importing templates...");
+    let Some(format) = Format::from_extension(file_path) else {
+        return Err(DmanError::InvalidInput(format!(
+            "{file_path:?} is not a toml, json or yaml file"
+        )));
+    };
 
-    // import template(s)
-    for template in templates {
-        println!("Importing template {:?}", template.name);
-        create_template(
-            Some(template.name),
-            Some(template.path),
-            Some(template.git_path),
-        );
+    let file = std::fs::read_to_string(file_path)?;
+    let parse_error =
+        |e: String| DmanError::InvalidInput(format!("Couldn't parse {file_path:?}: {e}"));
+
+    // Templates of every format are read as toml values, so they're checked the same way
+    match format {
+        Format::Toml => toml::from_str(&file).map_err(|source| DmanError::TomlParse {
+            file: file_path.to_string_lossy().to_string(),
+            source,
+        }),
+        Format::Json => serde_json::from_str(&file).map_err(|e| parse_error(e.to_string())),
+        Format::Yaml => serde_yaml::from_str(&file).map_err(|e| parse_error(e.to_string())),
+    }
+}
+
+/// Check if file has shape of dman export, table of templates with git_path
+fn is_exported(file_path: &Path) -> bool {
+    read_export_file(file_path).is_ok_and(|value| {
+        value.as_table().is_some_and(|table| {
+            !table.is_empty()
+                && table
+                    .values()
+                    .all(|template| template.get("git_path").is_some())
+        })
+    })
+}
+
+/// Read templates from toml, json or yaml file written by `dman export`
+///
+/// # Errors
+///
+/// * If the file isn't a toml, json or yaml file or it can't be parsed
+/// * If any template doesn't contain a name, path or git_path
+pub fn read_exported(file_path: &Path) -> Result<Vec<Template>, DmanError> {
+    let file_contents = read_export_file(file_path)?;
+    let mut templates: Vec<Template> = Vec::new();
+
+    let Some(table) = file_contents.as_table() else {
+        return Err(DmanError::InvalidInput(format!(
+            "{file_path:?} doesn't contain any templates"
+        )));
+    };
+
+    // put templates to vector
+    for (key, value) in table.iter() {
+        #[cfg(debug_assertions)]
+        {
+            println!("{key}: {value}");
+        }
+
+        // check if file contains table name
+        // otherwise it would use wrong key as table name and couldn't find the values
+
+        match key.as_str() {
+            "name" | "path" | "git_path" => {
+                return Err(DmanError::InvalidInput("Table name missing".to_string()));
+            }
+            _ => {}
+        }
+
+        // check if file contains valid values
+        // if not, panic
+        // if yes, put them in a vector
+        // and then add them to the templates vector
+
+        for field in ["name", "path", "git_path"] {
+            if value.get(field).is_none() {
+                return Err(DmanError::InvalidInput(format!(
+                    "Template {key:?} does not contain a {field}"
+                )));
+            }
+        }
+
+        // Exported templates have all their settings, not only name, path and git_path
+        let template: Template =
+            value
+                .clone()
+                .try_into()
+                .map_err(|source| DmanError::TomlParse {
+                    file: file_path.to_string_lossy().to_string(),
+                    source,
+                })?;
+
+        templates.push(template);
     }
+
+    Ok(templates)
 }
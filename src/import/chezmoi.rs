@@ -0,0 +1,250 @@
+use super::{folder_name, origin_url, target_path, Translation};
+use crate::lib::{DeployFile, DeployMode, DmanError, Template};
+use mytools::env::get_home_folder;
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Attributes of source state, that change meaning of file, so it can't be symlinked
+const UNSUPPORTED: [&str; 7] = [
+    "run_",
+    "modify_",
+    "remove_",
+    "create_",
+    "symlink_",
+    "encrypted_",
+    "external_",
+];
+
+/// Attributes setting permissions, they're dropped, because files are symlinked
+const PERMISSIONS: [&str; 5] = ["exact_", "private_", "readonly_", "empty_", "executable_"];
+
+/// Name of file in home folder, translated from name in source state
+#[derive(Debug)]
+struct Name {
+    target: String,
+    /// Attributes that were dropped
+    dropped: Vec<&'static str>,
+}
+
+/// Translate name of source state, e.g. private_dot_ssh -> .ssh
+///
+/// Returns reason, if the file can't be translated
+fn translate_name(name: &str) -> Result<Name, String> {
+    // literal_ prefix and .literal suffix stop parsing of attributes
+    let (name, suffix_literal) = match name.strip_suffix(".literal") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if let Some(literal) = name.strip_prefix("literal_") {
+        return Ok(Name {
+            target: literal.to_string(),
+            dropped: Vec::new(),
+        });
+    }
+
+    if let Some(attribute) = UNSUPPORTED
+        .iter()
+        .find(|attribute| name.starts_with(*attribute))
+    {
+        return Err(format!("{attribute} attribute"));
+    }
+    if !suffix_literal && name.ends_with(".tmpl") {
+        return Err("template, templates of chezmoi use Go syntax".to_string());
+    }
+
+    let mut rest = name;
+    let mut dropped = Vec::new();
+    // Attributes have fixed order, so one pass is enough
+    for attribute in PERMISSIONS {
+        if let Some(stripped) = rest.strip_prefix(attribute) {
+            rest = stripped;
+            dropped.push(attribute);
+        }
+    }
+
+    let target = match rest.strip_prefix("dot_") {
+        Some(stripped) => format!(".{stripped}"),
+        None => rest.to_string(),
+    };
+
+    Ok(Name { target, dropped })
+}
+
+/// Check if every file in folder has the same name in home folder, so folder can be
+/// symlinked whole
+fn is_verbatim(folder: &Path) -> Result<bool, DmanError> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Files starting with '.' are ignored by chezmoi, so they can't be deployed
+        if name.starts_with('.')
+            || !translate_name(&name).is_ok_and(|translated| translated.target == name)
+            || (entry.file_type()?.is_dir() && !is_verbatim(&entry.path())?)
+        {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Files of template and what couldn't be translated
+#[derive(Debug, Default)]
+struct Walked {
+    files: Vec<DeployFile>,
+    untranslated: Vec<String>,
+    dropped: BTreeSet<&'static str>,
+}
+
+/// Translate files in folder of source state
+fn walk(root: &Path, source: &Path, target: &Path, walked: &mut Walked) -> Result<(), DmanError> {
+    let mut entries: Vec<_> = fs::read_dir(root.join(source))?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let home = PathBuf::from(get_home_folder());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let source = source.join(&name);
+
+        if name.starts_with(".chezmoi") {
+            if !matches!(name.as_str(), ".chezmoiroot" | ".chezmoiversion") {
+                walked
+                    .untranslated
+                    .push(format!("{}, it's config of chezmoi", source.display()));
+            }
+            continue;
+        }
+        // Ignored by chezmoi, e.g. .git
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let translated = match translate_name(&name) {
+            Ok(translated) => translated,
+            Err(reason) => {
+                walked
+                    .untranslated
+                    .push(format!("{}, {reason}", source.display()));
+                continue;
+            }
+        };
+        walked.dropped.extend(translated.dropped);
+
+        let target = target.join(&translated.target);
+        let is_real_folder = home
+            .join(&target)
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_dir());
+        // Contents of folder with renamed files, or of existing folder like .config,
+        // have to be linked one by one
+        if entry.file_type()?.is_dir() && (is_real_folder || !is_verbatim(&entry.path())?) {
+            walk(root, &source, &target, walked)?;
+            continue;
+        }
+
+        walked.files.push(DeployFile {
+            source: source.to_string_lossy().to_string(),
+            target: target_path(&home, &target),
+        });
+    }
+
+    Ok(())
+}
+
+/// Translate source folder of chezmoi, files are symlinked to home folder
+///
+/// Names are translated, e.g. dot_bashrc -> .bashrc, files, that chezmoi creates
+/// in other way, like templates, scripts or encrypted files, are listed as untranslated
+///
+/// # Errors
+///
+/// * If source folder can't be read
+pub fn translate(source_dir: &Path) -> Result<Translation, DmanError> {
+    // Source state can be in subfolder of repository
+    let root = match fs::read_to_string(source_dir.join(".chezmoiroot")) {
+        Ok(root) => PathBuf::from(root.trim()),
+        Err(_) => PathBuf::new(),
+    };
+
+    let mut walked = Walked::default();
+    walk(source_dir, &root, Path::new(""), &mut walked)?;
+
+    let mut translation = Translation {
+        untranslated: walked.untranslated,
+        ..Default::default()
+    };
+    if !walked.dropped.is_empty() {
+        let dropped: Vec<&str> = walked.dropped.into_iter().collect();
+        translation.untranslated.push(format!(
+            "attributes {}, permissions of files aren't changed",
+            dropped.join(", ")
+        ));
+    }
+
+    let git_path = origin_url(source_dir).unwrap_or_default();
+    if git_path.is_empty() {
+        translation
+            .untranslated
+            .push(format!("git_path, {source_dir:?} has no remote"));
+    }
+
+    translation.templates.push(Template {
+        name: folder_name(source_dir),
+        path: source_dir.to_string_lossy().to_string(),
+        git_path,
+        deploy: DeployMode::Symlink,
+        files: walked.files,
+        ..Default::default()
+    });
+
+    Ok(translation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_name;
+
+    fn target(name: &str) -> String {
+        translate_name(name).unwrap().target
+    }
+
+    #[test]
+    fn dot_prefix_becomes_dot() {
+        assert_eq!(target("dot_bashrc"), ".bashrc");
+        assert_eq!(target("dot_config"), ".config");
+        assert_eq!(target("README.md"), "README.md");
+    }
+
+    #[test]
+    fn permission_attributes_are_dropped() {
+        let name = translate_name("private_dot_ssh").unwrap();
+        assert_eq!(name.target, ".ssh");
+        assert_eq!(name.dropped, ["private_"]);
+
+        let name = translate_name("private_executable_dot_script").unwrap();
+        assert_eq!(name.target, ".script");
+        assert_eq!(name.dropped, ["private_", "executable_"]);
+
+        let name = translate_name("executable_backup.sh").unwrap();
+        assert_eq!(name.target, "backup.sh");
+        assert_eq!(name.dropped, ["executable_"]);
+    }
+
+    #[test]
+    fn literal_stops_attributes() {
+        assert_eq!(target("literal_dot_vim"), "dot_vim");
+        assert_eq!(target("dot_vimrc.tmpl.literal"), ".vimrc.tmpl");
+    }
+
+    #[test]
+    fn unsupported_names_are_rejected() {
+        assert!(translate_name("run_once_install.sh").is_err());
+        assert!(translate_name("encrypted_private_dot_netrc").is_err());
+        assert!(translate_name("symlink_dot_vimrc").is_err());
+        assert!(translate_name("dot_gitconfig.tmpl").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use super::{folder_name, origin_url, Translation};
+use crate::lib::{DeployFile, DeployMode, DmanError, Template};
+use serde_yaml::{Mapping, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Names of dotbot config, that are looked for in repository
+const CONFIG_FILES: [&str; 3] = ["install.conf.yaml", "install.conf.yml", "install.conf.json"];
+
+/// Get dotbot config in folder
+pub fn config_file(folder: &Path) -> Option<PathBuf> {
+    CONFIG_FILES
+        .iter()
+        .map(|name| folder.join(name))
+        .find(|config| config.is_file())
+}
+
+/// Text of command of shell directive, which is string, list or table with command
+fn shell_command(value: &Value) -> String {
+    match value {
+        Value::String(command) => command.clone(),
+        Value::Sequence(parts) => parts.first().map(shell_command).unwrap_or_default(),
+        Value::Mapping(options) => options
+            .get("command")
+            .map(shell_command)
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+/// Add links matched by glob, files matched now are added, not those created later
+fn add_glob(
+    base: &Path,
+    target: &str,
+    pattern: &str,
+    prefix: &str,
+    files: &mut Vec<DeployFile>,
+) -> Result<(), DmanError> {
+    let pattern = base.join(pattern);
+    let matches = glob::glob(&pattern.to_string_lossy())
+        .map_err(|e| DmanError::InvalidInput(format!("Invalid glob {pattern:?}: {e}")))?;
+
+    for source in matches.flatten() {
+        let (Ok(relative), Some(name)) = (source.strip_prefix(base), source.file_name()) else {
+            continue;
+        };
+        files.push(DeployFile {
+            source: relative.to_string_lossy().to_string(),
+            target: format!(
+                "{}/{prefix}{}",
+                target.trim_end_matches('/'),
+                name.to_string_lossy()
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Translate one link of link directive
+fn add_link(
+    base: &Path,
+    target: &str,
+    value: &Value,
+    defaults: &Mapping,
+    translation: &mut Translation,
+    files: &mut Vec<DeployFile>,
+) -> Result<(), DmanError> {
+    let empty = Mapping::new();
+    let options = value.as_mapping().unwrap_or(&empty);
+    let option = |key: &str| options.get(key).or_else(|| defaults.get(key));
+
+    // Without path, source is the target without leading '.'
+    let source = match value {
+        Value::String(source) => source.clone(),
+        _ => match option("path").and_then(Value::as_str) {
+            Some(source) => source.to_string(),
+            None => Path::new(target)
+                .file_name()
+                .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+                .unwrap_or_default(),
+        },
+    };
+
+    if let Some(condition) = option("if").and_then(Value::as_str) {
+        translation
+            .untranslated
+            .push(format!("link {target} with condition `{condition}`"));
+        return Ok(());
+    }
+
+    if Path::new(&source).is_absolute() || source.starts_with('~') {
+        translation
+            .untranslated
+            .push(format!("link {target}, {source} is outside of repository"));
+        return Ok(());
+    }
+
+    if option("glob").and_then(Value::as_bool) == Some(true) {
+        let prefix = option("prefix").and_then(Value::as_str).unwrap_or_default();
+        return add_glob(base, target, &source, prefix, files);
+    }
+
+    files.push(DeployFile {
+        source,
+        target: target.to_string(),
+    });
+
+    Ok(())
+}
+
+/// Translate dotbot repository, links of install.conf.yaml become files of template
+///
+/// Shell commands and other directives are listed as untranslated
+///
+/// # Errors
+///
+/// * If config doesn't exist or it isn't valid YAML or JSON list of directives
+pub fn translate(path: &Path) -> Result<Translation, DmanError> {
+    let config = if path.is_dir() {
+        config_file(path)
+            .ok_or_else(|| DmanError::NotFound(format!("dotbot config in {path:?}")))?
+    } else {
+        path.to_path_buf()
+    };
+    // Paths of config are relative to folder of config
+    let base = config.parent().unwrap_or(path).to_path_buf();
+
+    let directives: Vec<Mapping> = serde_yaml::from_str(&fs::read_to_string(&config)?)
+        .map_err(|e| DmanError::InvalidInput(format!("Couldn't parse {config:?}: {e}")))?;
+
+    let mut translation = Translation::default();
+    let mut files = Vec::new();
+    let mut defaults = Mapping::new();
+
+    for directive in directives.iter().flat_map(|directive| directive.iter()) {
+        let (Some(name), value) = (directive.0.as_str(), directive.1) else {
+            continue;
+        };
+
+        match name {
+            "defaults" => {
+                if let Some(link) = value.get("link").and_then(Value::as_mapping) {
+                    defaults = link.clone();
+                }
+            }
+            "link" => {
+                let Some(links) = value.as_mapping() else {
+                    continue;
+                };
+                for (target, link) in links {
+                    let Some(target) = target.as_str() else {
+                        continue;
+                    };
+                    add_link(&base, target, link, &defaults, &mut translation, &mut files)?;
+                }
+            }
+            "shell" => {
+                for command in value.as_sequence().into_iter().flatten() {
+                    translation.untranslated.push(format!(
+                        "shell command `{}`, set it as hook if it's needed",
+                        shell_command(command)
+                    ));
+                }
+            }
+            _ => translation
+                .untranslated
+                .push(format!("{name} directive of {config:?}")),
+        }
+    }
+
+    let git_path = origin_url(&base).unwrap_or_default();
+    if git_path.is_empty() {
+        translation
+            .untranslated
+            .push(format!("git_path, {base:?} has no remote"));
+    }
+
+    translation.templates.push(Template {
+        name: folder_name(&base),
+        path: base.to_string_lossy().to_string(),
+        git_path,
+        deploy: DeployMode::Symlink,
+        files,
+        ..Default::default()
+    });
+
+    Ok(translation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_folder, translate};
+    use std::fs;
+
+    const CONFIG: &str = "\
+- defaults:
+    link:
+      relink: true
+- link:
+    ~/.vimrc:
+    ~/.bashrc: bash/bashrc
+    ~/.config/nvim:
+      path: nvim
+    ~/.zshrc:
+      if: '[ `uname` = Darwin ]'
+      path: zshrc
+    ~/.gitconfig: ~/shared/gitconfig
+    ~/.local/bin/:
+      glob: true
+      path: bin/*
+      prefix: dot-
+- shell:
+  - [git submodule update --init, Installing submodules]
+  - command: ./setup.sh
+- clean: ['~']
+";
+
+    fn files(name: &str, config_name: &str, config: &str) -> (Vec<(String, String)>, Vec<String>) {
+        let folder = test_folder(name);
+        fs::create_dir(folder.join("bin")).unwrap();
+        fs::write(folder.join("bin/a"), "").unwrap();
+        fs::write(folder.join("bin/b"), "").unwrap();
+        fs::write(folder.join(config_name), config).unwrap();
+
+        let translation = translate(&folder).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        let files = translation.templates[0]
+            .files
+            .iter()
+            .map(|file| (file.source.clone(), file.target.clone()))
+            .collect();
+
+        (files, translation.untranslated)
+    }
+
+    fn file(source: &str, target: &str) -> (String, String) {
+        (source.to_string(), target.to_string())
+    }
+
+    #[test]
+    fn links_are_translated() {
+        let (files, _) = files("dotbot-links", "install.conf.yaml", CONFIG);
+
+        assert_eq!(
+            files,
+            [
+                // Null value links file named like target without leading '.'
+                file("vimrc", "~/.vimrc"),
+                file("bash/bashrc", "~/.bashrc"),
+                file("nvim", "~/.config/nvim"),
+                file("bin/a", "~/.local/bin/dot-a"),
+                file("bin/b", "~/.local/bin/dot-b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn untranslatable_parts_are_listed() {
+        let (_, untranslated) = files("dotbot-untranslated", "install.conf.yaml", CONFIG);
+
+        let listed = |text: &str| untranslated.iter().any(|item| item.contains(text));
+        assert!(listed("~/.zshrc with condition"));
+        assert!(listed("~/shared/gitconfig is outside of repository"));
+        assert!(listed("`git submodule update --init`"));
+        assert!(listed("`./setup.sh`"));
+        assert!(listed("clean directive"));
+        assert!(!listed("defaults"));
+    }
+
+    #[test]
+    fn json_config_is_read() {
+        let config = r#"[{"link": {"~/.vimrc": null, "~/.tmux.conf": "tmux.conf"}}]"#;
+        let (files, _) = files("dotbot-json", "install.conf.json", config);
+
+        assert_eq!(
+            files,
+            [file("vimrc", "~/.vimrc"), file("tmux.conf", "~/.tmux.conf")]
+        );
+    }
+}
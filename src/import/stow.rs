@@ -0,0 +1,298 @@
+use super::{origin_url, target_path, Translation};
+use crate::lib::{expand_home, DeployFile, DeployMode, DmanError, Template};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Files ignored by Stow, when package has no ignore list
+const IGNORED: [&str; 7] = [
+    ".git",
+    ".gitignore",
+    ".gitmodules",
+    ".stow-local-ignore",
+    "CVS",
+    "COPYING",
+    "_darcs",
+];
+
+/// Options of .stowrc, that are translated
+#[derive(Debug)]
+struct Options {
+    /// Folder packages are linked into, parent of Stow folder by default
+    target: PathBuf,
+    /// `dot-` prefixes are replaced by '.', like with `stow --dotfiles`
+    dotfiles: bool,
+}
+
+fn is_ignored(name: &str) -> bool {
+    IGNORED.contains(&name)
+        || ["README", "LICENSE"]
+            .iter()
+            .any(|ignored| name == *ignored || name.starts_with(&format!("{ignored}.")))
+}
+
+/// Read options from .stowrc in Stow folder
+fn read_options(stow_dir: &Path, untranslated: &mut Vec<String>) -> Result<Options, DmanError> {
+    let mut options = Options {
+        target: stow_dir.parent().unwrap_or(stow_dir).to_path_buf(),
+        dotfiles: false,
+    };
+
+    let stowrc = stow_dir.join(".stowrc");
+    if !stowrc.exists() {
+        return Ok(options);
+    }
+
+    let contents = fs::read_to_string(&stowrc)?;
+    let mut words = contents.split_whitespace();
+    while let Some(option) = words.next() {
+        let target = match option {
+            "-t" | "--target" => words.next(),
+            _ => option.strip_prefix("--target="),
+        };
+
+        if let Some(target) = target {
+            options.target = stow_dir.join(expand_home(target));
+        } else if option == "--dotfiles" {
+            options.dotfiles = true;
+        } else {
+            untranslated.push(format!("option {option} in {stowrc:?}"));
+        }
+    }
+
+    Ok(options)
+}
+
+/// Path of file after linking, `dot-` prefixes are replaced with `--dotfiles`
+fn link_name(relative: &Path, options: &Options) -> PathBuf {
+    if !options.dotfiles {
+        return relative.to_path_buf();
+    }
+
+    relative
+        .iter()
+        .map(|part| {
+            let part = part.to_string_lossy();
+            match part.strip_prefix("dot-") {
+                Some(name) => format!(".{name}"),
+                None => part.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Get folders inside package, relative to the package
+fn package_folders(
+    package: &Path,
+    relative: &Path,
+    folders: &mut Vec<PathBuf>,
+) -> Result<(), DmanError> {
+    for entry in fs::read_dir(package.join(relative))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if entry.file_type()?.is_dir() && !is_ignored(&name.to_string_lossy()) {
+            let folder = relative.join(name);
+            package_folders(package, &folder, folders)?;
+            folders.push(folder);
+        }
+    }
+
+    Ok(())
+}
+
+/// Add files of package folder to template
+///
+/// Folder is linked whole, unless other package uses it too, or it exists in target,
+/// then its contents are linked one by one, like Stow does
+fn add_files(
+    package: &Path,
+    relative: &Path,
+    shared: &HashMap<PathBuf, usize>,
+    options: &Options,
+    files: &mut Vec<DeployFile>,
+) -> Result<(), DmanError> {
+    let mut entries: Vec<_> = fs::read_dir(package.join(relative))?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        if is_ignored(&name.to_string_lossy()) {
+            continue;
+        }
+
+        let path = relative.join(&name);
+        let link = link_name(&path, options);
+        let target = options.target.join(&link);
+        let is_real_folder = target
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_dir());
+
+        if entry.file_type()?.is_dir()
+            && (shared.get(&link).is_some_and(|count| *count > 1) || is_real_folder)
+        {
+            add_files(package, &path, shared, options, files)?;
+        } else {
+            let package_name = package.file_name().unwrap_or_default();
+            files.push(DeployFile {
+                source: Path::new(package_name)
+                    .join(&path)
+                    .to_string_lossy()
+                    .to_string(),
+                target: target_path(&options.target, &link),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Translate GNU Stow folder, every package becomes template with its files symlinked
+///
+/// # Errors
+///
+/// * If Stow folder or its package can't be read
+pub fn translate(stow_dir: &Path) -> Result<Translation, DmanError> {
+    let mut translation = Translation::default();
+    let options = read_options(stow_dir, &mut translation.untranslated)?;
+
+    let git_path = origin_url(stow_dir).unwrap_or_default();
+    if git_path.is_empty() {
+        translation
+            .untranslated
+            .push(format!("git_path, {stow_dir:?} has no remote"));
+    }
+
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(stow_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && !name.starts_with('.') && !is_ignored(&name) {
+            packages.push(entry.path());
+        }
+    }
+    packages.sort();
+
+    // Folders used by more packages can't be linked by one of them
+    let mut shared: HashMap<PathBuf, usize> = HashMap::new();
+    for package in &packages {
+        let mut folders = Vec::new();
+        package_folders(package, Path::new(""), &mut folders)?;
+        for folder in folders {
+            *shared.entry(link_name(&folder, &options)).or_default() += 1;
+        }
+    }
+
+    for package in packages {
+        if package.join(".stow-local-ignore").exists() {
+            translation.untranslated.push(format!(
+                "ignore list {:?}, check files of the template",
+                package.join(".stow-local-ignore")
+            ));
+        }
+
+        let mut files = Vec::new();
+        add_files(&package, Path::new(""), &shared, &options, &mut files)?;
+        if files.is_empty() {
+            translation
+                .untranslated
+                .push(format!("package {package:?}, it has no files"));
+            continue;
+        }
+
+        translation.templates.push(Template {
+            name: package
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            path: stow_dir.to_string_lossy().to_string(),
+            git_path: git_path.clone(),
+            deploy: DeployMode::Symlink,
+            files,
+            ..Default::default()
+        });
+    }
+
+    Ok(translation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_folder, is_ignored, translate};
+    use std::fs;
+
+    #[test]
+    fn default_ignore_list() {
+        for name in [".git", ".gitignore", ".stow-local-ignore", "COPYING", "CVS"] {
+            assert!(is_ignored(name), "{name} should be ignored");
+        }
+        for name in ["README", "README.md", "LICENSE", "LICENSE.txt"] {
+            assert!(is_ignored(name), "{name} should be ignored");
+        }
+        for name in ["bashrc", "dot-gitignore", "READMEs", "license"] {
+            assert!(!is_ignored(name), "{name} shouldn't be ignored");
+        }
+    }
+
+    #[test]
+    fn packages_are_translated_without_ignored_files() {
+        let stow_dir = test_folder("stow");
+        let target = stow_dir.join("target");
+        fs::write(
+            stow_dir.join(".stowrc"),
+            format!("--dotfiles --target={}", target.display()),
+        )
+        .unwrap();
+        fs::create_dir_all(stow_dir.join("bash")).unwrap();
+        fs::write(stow_dir.join("bash/dot-bashrc"), "").unwrap();
+        fs::write(stow_dir.join("bash/README.md"), "").unwrap();
+        fs::create_dir_all(stow_dir.join("nvim/dot-config/nvim")).unwrap();
+        fs::write(stow_dir.join("nvim/dot-config/nvim/init.lua"), "").unwrap();
+        fs::write(stow_dir.join("nvim/.stow-local-ignore"), "init.lua").unwrap();
+        fs::create_dir_all(stow_dir.join("git/dot-config/git")).unwrap();
+        fs::write(stow_dir.join("git/dot-config/git/config"), "").unwrap();
+        fs::create_dir_all(stow_dir.join("docs")).unwrap();
+        fs::write(stow_dir.join("docs/LICENSE"), "").unwrap();
+
+        let translation = translate(&stow_dir).unwrap();
+        fs::remove_dir_all(&stow_dir).unwrap();
+
+        let files: Vec<(&str, Vec<(String, String)>)> = translation
+            .templates
+            .iter()
+            .map(|template| {
+                let files = template
+                    .files
+                    .iter()
+                    .map(|file| {
+                        let target = file.target.strip_prefix(&*target.to_string_lossy());
+                        (file.source.clone(), target.unwrap_or_default().to_string())
+                    })
+                    .collect();
+                (template.name.as_str(), files)
+            })
+            .collect();
+        let file = |source: &str, target: &str| (source.to_string(), target.to_string());
+
+        // .config is used by 2 packages, so its folders are linked one by one
+        assert_eq!(
+            files,
+            [
+                ("bash", vec![file("bash/dot-bashrc", "/.bashrc")]),
+                ("git", vec![file("git/dot-config/git", "/.config/git")]),
+                ("nvim", vec![file("nvim/dot-config/nvim", "/.config/nvim")]),
+            ]
+        );
+
+        let listed = |text: &str| {
+            translation
+                .untranslated
+                .iter()
+                .any(|item| item.contains(text))
+        };
+        assert!(listed("ignore list"));
+        assert!(listed("package") && listed("docs"));
+    }
+}
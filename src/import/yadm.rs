@@ -0,0 +1,82 @@
+use super::{origin_url, Translation};
+use crate::lib::{expand_home, DeployFile, DeployMode, DmanError, Template};
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use std::path::Path;
+
+/// Where yadm repository is cloned as normal repository, yadm's one is bare
+const CLONE_PATH: &str = "~/.dotfiles";
+
+/// Translate file tracked by yadm, None if dman can't deploy it
+fn translate_file(file: &str, untranslated: &mut Vec<String>) -> Option<DeployFile> {
+    // Alternates and templates, e.g. .gitconfig##os.Linux
+    if file.contains("##") {
+        untranslated.push(format!(
+            "{file}, alternates and templates of yadm aren't supported"
+        ));
+        return None;
+    }
+
+    for config in [".config/yadm/", ".yadm/"] {
+        let Some(name) = file.strip_prefix(config) else {
+            continue;
+        };
+
+        untranslated.push(match name {
+            "bootstrap" => format!("{file}, set it as post_clone hook"),
+            "encrypt" => format!("{file}, add files it lists with `dman secret add`"),
+            _ => format!("{file}, it's used by yadm itself"),
+        });
+        return None;
+    }
+
+    Some(DeployFile {
+        source: file.to_string(),
+        target: format!("~/{file}"),
+    })
+}
+
+/// Translate bare repository of yadm, every tracked file is symlinked to home folder
+///
+/// Repository is cloned to ~/.dotfiles, origin of yadm repository is used as git_path,
+/// or the repository itself, if it has no origin
+///
+/// # Errors
+///
+/// * If repository isn't bare or it can't be read
+pub fn translate(path: &Path) -> Result<Translation, DmanError> {
+    let repo = Repository::open(path)?;
+    if !repo.is_bare() {
+        return Err(DmanError::InvalidInput(format!(
+            "{path:?} isn't bare repository of yadm"
+        )));
+    }
+
+    let mut translation = Translation::default();
+    let mut tracked = Vec::new();
+
+    let tree = repo.head()?.peel_to_tree()?;
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            tracked.push(format!("{root}{}", entry.name().unwrap_or_default()));
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    let files = tracked
+        .iter()
+        .filter_map(|file| translate_file(file, &mut translation.untranslated))
+        .collect();
+
+    let git_path = origin_url(path).unwrap_or_else(|| path.to_string_lossy().to_string());
+
+    translation.templates.push(Template {
+        name: "yadm".to_string(),
+        path: expand_home(CLONE_PATH).to_string_lossy().to_string(),
+        git_path,
+        deploy: DeployMode::Symlink,
+        files,
+        ..Default::default()
+    });
+
+    Ok(translation)
+}
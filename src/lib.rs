@@ -1,22 +1,291 @@
 #![allow(dead_code)]
 
-use mytools::{env::get_home_folder, pretty_panic};
-use serde::Deserialize;
+#[path = "./config.rs"]
+pub mod config;
+#[path = "./libgit2-rs/credentials.rs"]
+pub mod credentials;
+#[path = "./error.rs"]
+pub mod error;
+#[path = "./machine.rs"]
+pub mod machine;
+#[path = "./remote.rs"]
+pub mod remote;
+
+pub use error::DmanError;
+use machine::Machine;
+use mytools::{env::get_home_folder, warn};
+pub use remote::check_if_remote_exists;
+use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
     fs::{self, ReadDir},
-    path::Path,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
 use tabled::Tabled;
 
-#[derive(Clone, Debug, Default, Deserialize, Tabled)]
+/// Held while asking user for input, so prompts of templates pulled in parallel don't mix
+pub static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set by --offline flag or `offline` in config.toml, remotes aren't contacted
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turn offline mode on or off for the rest of the run
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Check if dman runs in offline mode
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// How pull integrates remote changes, when local and remote branch diverged
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullStrategy {
+    /// Refuse to pull diverged branch
+    FfOnly,
+    /// Create merge commit
+    #[default]
+    Merge,
+    /// Replay local commits on top of remote branch
+    Rebase,
+}
+
+impl FromStr for PullStrategy {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ff-only" => Ok(PullStrategy::FfOnly),
+            "merge" => Ok(PullStrategy::Merge),
+            "rebase" => Ok(PullStrategy::Rebase),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown pull strategy {s:?}, use ff-only, merge or rebase"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PullStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullStrategy::FfOnly => write!(f, "ff-only"),
+            PullStrategy::Merge => write!(f, "merge"),
+            PullStrategy::Rebase => write!(f, "rebase"),
+        }
+    }
+}
+
+/// How files of template get to their final location
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeployMode {
+    /// Repository is used directly at template path
+    #[default]
+    InPlace,
+    /// Files of template are symlinked to their targets
+    Symlink,
+    /// Files of template are copied to their targets
+    Copy,
+}
+
+impl DeployMode {
+    fn is_in_place(&self) -> bool {
+        *self == DeployMode::InPlace
+    }
+}
+
+impl FromStr for DeployMode {
+    type Err = DmanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in-place" => Ok(DeployMode::InPlace),
+            "symlink" => Ok(DeployMode::Symlink),
+            "copy" => Ok(DeployMode::Copy),
+            _ => Err(DmanError::InvalidInput(format!(
+                "Unknown deploy mode {s:?}, use in-place, symlink or copy"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DeployMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeployMode::InPlace => write!(f, "in-place"),
+            DeployMode::Symlink => write!(f, "symlink"),
+            DeployMode::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+/// File or folder of template, that's deployed outside of repository
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct DeployFile {
+    /// Path relative to template path
+    pub source: String,
+    /// Where file is deployed, `~` is expanded to home folder
+    pub target: String,
+}
+
+/// Version of template files written by this dman
+///
+/// * 1 - only name, path and git_path, file has no version
+/// * 2 - optional branch, remote, description, tags, enabled, pull_strategy, hosts,
+///   machine_tags, deploy, files, secrets and hooks
+pub const TEMPLATE_VERSION: u32 = 2;
+
+/// Remote used, when template doesn't set one
+pub const DEFAULT_REMOTE: &str = "origin";
+
+#[derive(Clone, Debug, Deserialize, Serialize, Tabled)]
 pub struct Template {
     pub name: String,
     pub path: String,
     pub git_path: String,
+    /// Branch to pull, every local branch is pulled if it's not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub branch: Option<String>,
+    /// Name of the remote, DEFAULT_REMOTE if it's not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub remote: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub tags: Vec<String>,
+    /// Disabled templates are skipped by pull-all
+    #[serde(default = "enabled_by_default")]
+    #[tabled(skip)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub pull_strategy: Option<PullStrategy>,
+    /// Hostname patterns of machines template applies to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub hosts: Vec<String>,
+    /// Tags from machine.toml of machines template applies to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub machine_tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "DeployMode::is_in_place")]
+    #[tabled(skip)]
+    pub deploy: DeployMode,
+    /// Files deployed by `dman link`, unused when template is used in place
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub files: Vec<DeployFile>,
+    /// Files relative to template path, that are committed only encrypted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tabled(skip)]
+    pub secrets: Vec<String>,
+    /// Shell command run before repository is cloned, in parent folder of path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub pre_clone: Option<String>,
+    /// Shell command run after repository is cloned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub post_clone: Option<String>,
+    /// Shell command run before pull, pull is cancelled if it fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub pre_pull: Option<String>,
+    /// Shell command run after pull changed something
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub post_pull: Option<String>,
+    /// Shell command run before push, push is cancelled if it fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub pre_push: Option<String>,
+    /// Shell command run after push
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[tabled(skip)]
+    pub post_push: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Default for Template {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            path: String::new(),
+            git_path: String::new(),
+            branch: None,
+            remote: None,
+            description: None,
+            tags: Vec::new(),
+            enabled: true,
+            pull_strategy: None,
+            hosts: Vec::new(),
+            machine_tags: Vec::new(),
+            deploy: DeployMode::InPlace,
+            files: Vec::new(),
+            secrets: Vec::new(),
+            pre_clone: None,
+            post_clone: None,
+            pre_pull: None,
+            post_pull: None,
+            pre_push: None,
+            post_push: None,
+        }
+    }
+}
+
+impl Template {
+    /// Name of the remote to pull from and push to
+    pub fn remote_name(&self) -> &str {
+        self.remote.as_deref().unwrap_or(DEFAULT_REMOTE)
+    }
+
+    /// Check if template applies to machine
+    ///
+    /// Template without hosts and machine_tags applies everywhere,
+    /// otherwise hostname has to match one of hosts or machine needs one of machine_tags
+    pub fn applies_to(&self, machine: &Machine) -> bool {
+        if self.hosts.is_empty() && self.machine_tags.is_empty() {
+            return true;
+        }
+
+        self.hosts.iter().any(|host| machine.matches_host(host))
+            || self.machine_tags.iter().any(|tag| machine.has_tag(tag))
+    }
+}
+
+/// Replace leading `~` with home folder
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(get_home_folder()).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// Files without version were written before versioning was added
+fn first_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 struct Toml {
+    #[serde(default = "first_version")]
+    version: u32,
     template: Template,
 }
 
@@ -25,9 +294,8 @@ struct Toml {
 ///
 /// Returns the path to the template folder
 ///
-/// # Panics
+/// # Errors
 ///
-/// * If $HOME environment variable isn't set
 /// * If ~/.config/ can't be created
 /// * If ~/.config/dotfile-manager/ can't be created
 /// * If ~/.config/dotfile-manager/templates/ can't be created
@@ -36,44 +304,25 @@ struct Toml {
 ///
 /// ```
 /// use dotfile_manager::set_folders;
+/// # fn main() -> Result<(), dotfile_manager::DmanError> {
 ///
-/// let template_folder = set_folders();
+/// let template_folder = set_folders()?;
+/// # Ok(())
+/// # }
 /// ```
 #[allow(dead_code)]
-pub fn set_folders() -> String {
+pub fn set_folders() -> Result<String, DmanError> {
     let home_folder = get_home_folder();
     let config_folder_path = Path::new(&home_folder).join(".config");
 
     if !config_folder_path.exists() {
-        fs::create_dir(config_folder_path).expect("Can't create '~/.config/'");
+        fs::create_dir(config_folder_path)?;
     }
 
     let dman_folder = Path::new(&home_folder).join(".config/dotfile-manager");
 
     if !dman_folder.exists() {
-        fs::create_dir(&dman_folder).expect("Can't create '~/.config/dotfile-manager/");
-    }
-
-    // Create fake-git folder
-    // This is used to check if remote exists, because Repository::open() need a git repo
-    let fake_git_folder = Path::new(&home_folder).join(".local/share/dotfile-manager/fake-git");
-
-    if !fake_git_folder.exists() {
-        fs::create_dir_all(&fake_git_folder)
-            .expect("Can't create '~/.config/dotfile-manager/fake-git/");
-
-        // check if git is installed
-        which::which("git").expect("Git is not installed");
-        // git init
-        let mut cmd = std::process::Command::new("git");
-        cmd.arg("init");
-        cmd.current_dir(&fake_git_folder);
-        cmd.output().expect("Can't run git init");
-
-        // create readme inside fake-git folder
-        let fake_git_readme = "This is a fake git repository, used to check if remote exists";
-        fs::write(fake_git_folder.join("readme"), fake_git_readme)
-            .expect("Can't write to fake-git folder");
+        fs::create_dir(&dman_folder)?;
     }
 
     set_template_folder(&dman_folder)
@@ -81,45 +330,54 @@ pub fn set_folders() -> String {
 
 /// Check for template folder, else create one
 #[allow(dead_code)]
-fn set_template_folder(dman_folder: &Path) -> String {
+fn set_template_folder(dman_folder: &Path) -> Result<String, DmanError> {
     let template_folder = Path::new(&dman_folder).join("templates");
 
     // Create templates folder
     if !template_folder.exists() {
-        fs::create_dir(&template_folder)
-            .expect("Can't create '~/.config/dotfile-manager/templates/");
+        fs::create_dir(&template_folder)?;
     }
 
-    template_folder.to_str().unwrap().to_string()
+    Ok(template_folder.to_string_lossy().to_string())
 }
 
-fn get_fake_git_folder() -> String {
-    let home_folder = get_home_folder();
+/// Get ~/.local/share/dotfile-manager/, where dman keeps its own data
+///
+/// # Errors
+///
+/// * If folder can't be created
+pub fn get_data_folder() -> Result<PathBuf, DmanError> {
+    let data_folder = Path::new(&get_home_folder()).join(".local/share/dotfile-manager");
 
-    let fake_git_folder = Path::new(&home_folder).join(".local/share/dotfile-manager/fake-git");
+    if !data_folder.exists() {
+        fs::create_dir_all(&data_folder)?;
+    }
 
-    fake_git_folder.to_str().unwrap().to_string()
+    Ok(data_folder)
 }
 
 /// Get templates from filesystem ~/.config/templates/
 ///
-/// # Panics
+/// # Errors
 ///
-/// * If template folder can't be read
+/// * If template folder can't be created or read
 ///
 /// # Example
 ///
 /// ```
 /// use dotfile_manager::get_existing_templates;
+/// # fn main() -> Result<(), dotfile_manager::DmanError> {
 ///
-/// let templates = get_existing_templates();
+/// let templates = get_existing_templates()?;
+/// # Ok(())
+/// # }
 /// ```
 #[allow(dead_code)]
-pub fn get_existing_templates() -> ReadDir {
-    let template_folder = set_folders();
+pub fn get_existing_templates() -> Result<ReadDir, DmanError> {
+    let template_folder = set_folders()?;
 
     // Get templates from template folder
-    fs::read_dir(template_folder).unwrap()
+    Ok(fs::read_dir(template_folder)?)
 }
 
 /// Process file to Template struct
@@ -128,8 +386,9 @@ pub fn get_existing_templates() -> ReadDir {
 ///
 /// * file: &Result<fs::DirEntry, std::io::Error>
 ///
-/// # Panics
+/// # Errors
 ///
+/// * If template can't be read
 /// * If template can't be parsed
 ///
 /// # Example
@@ -137,30 +396,115 @@ pub fn get_existing_templates() -> ReadDir {
 /// ```
 /// use dotfile_manager::process_template_to_struct;
 /// use dotfile_manager::get_existing_templates;
+/// # fn main() -> Result<(), dotfile_manager::DmanError> {
 ///
-/// let templates = get_existing_templates();
+/// let templates = get_existing_templates()?;
 ///
 /// // Iterate over templates
 ///    for template in templates {
-/// let template = process_template_to_struct(&template);
+/// let template = process_template_to_struct(&template)?;
 /// }
+/// # Ok(())
+/// # }
 /// ```
 /// # Debug
 ///
 /// * Print template if debug_assertions is set
-pub fn process_template_to_struct(file: &Result<fs::DirEntry, std::io::Error>) -> Template {
-    let template_but_string = fs::read_to_string(file.as_ref().unwrap().path()).unwrap();
-    let template: Toml = toml::from_str(&template_but_string).expect("Couldn't parse");
-    // This is needed because I need to return clean Template struct, not Toml struct
-    // Toml struct contains Template struct inside
-    let template = template.template;
+pub fn process_template_to_struct(
+    file: &Result<fs::DirEntry, std::io::Error>,
+) -> Result<Template, DmanError> {
+    let file = match file {
+        Ok(file) => file.path(),
+        Err(error) => {
+            return Err(DmanError::Io(std::io::Error::new(
+                error.kind(),
+                error.to_string(),
+            )))
+        }
+    };
+
+    let (_version, template) = read_template_file(&file)?;
 
     #[cfg(debug_assertions)]
     {
         println!("{template:?}");
     }
 
-    template
+    Ok(template)
+}
+
+/// Read template file of any supported version
+///
+/// Returns version of the file and template
+///
+/// # Errors
+///
+/// * If file can't be read
+/// * If file can't be parsed
+/// * If file was written by newer dman
+pub fn read_template_file(file: &Path) -> Result<(u32, Template), DmanError> {
+    let template_but_string = fs::read_to_string(file)?;
+    let toml: Toml =
+        toml::from_str(&template_but_string).map_err(|source| DmanError::TomlParse {
+            file: file.to_string_lossy().to_string(),
+            source,
+        })?;
+
+    if toml.version > TEMPLATE_VERSION {
+        return Err(DmanError::InvalidInput(format!(
+            "{file:?} has template version {}, but this dman supports only up to {TEMPLATE_VERSION}, update dman",
+            toml.version
+        )));
+    }
+
+    // This is needed because I need to return clean Template struct, not Toml struct
+    // Toml struct contains Template struct inside
+    Ok((toml.version, toml.template))
+}
+
+/// Serialize template to contents of template file in current version
+///
+/// # Errors
+///
+/// * If template can't be serialized
+pub fn template_to_toml(template: &Template) -> Result<String, DmanError> {
+    // This is needed because i want toml to have table name
+    // [template]
+    // name = "..."
+    // path = "..."
+    // git_path = "..."
+    let toml = Toml {
+        version: TEMPLATE_VERSION,
+        template: template.clone(),
+    };
+
+    toml::to_string(&toml)
+        .map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize template: {e}")))
+}
+
+/// Write template into file at path, replacing the old one
+///
+/// # Errors
+///
+/// * If template can't be serialized or written
+pub fn write_template_file(path: &Path, template: &Template) -> Result<(), DmanError> {
+    // Write into temporary file first, so template isn't lost if writing fails
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, template_to_toml(template)?)?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Write template into its file in template folder, replacing the old one
+///
+/// # Errors
+///
+/// * If template can't be serialized or written
+pub fn save_template(template: &Template) -> Result<(), DmanError> {
+    let path = Path::new(&set_folders()?).join(format!("{}.toml", template.name));
+
+    write_template_file(&path, template)
 }
 
 enum Matching {
@@ -169,178 +513,154 @@ enum Matching {
     GitPath,
 }
 
+/// Find template, whose name, path or git path matches user input
+///
+/// # Errors
+///
+/// * If no argument is given
+/// * If no template matches
+/// * If more than one template matches
+/// * If templates can't be read
 #[allow(dead_code)]
 pub fn match_user_input_with_existing_templates(
     name: Option<String>,
     path: Option<String>,
     git_path: Option<String>,
-) -> Template {
-    let templates = get_existing_templates();
-
+) -> Result<Template, DmanError> {
     // How to match input with saved templates
-    let matching: Matching;
-
-    if name.is_some() {
+    let (matching, user_input) = if let Some(name) = name {
         println!("Matching by name");
-        matching = Matching::Name;
-    } else if path.is_some() {
+        (Matching::Name, name)
+    } else if let Some(path) = path {
         println!("Matching by path");
-        matching = Matching::Path;
-    } else if git_path.is_some() {
+        (Matching::Path, path)
+    } else if let Some(git_path) = git_path {
         println!("Matching by git-path");
-        matching = Matching::GitPath;
+        (Matching::GitPath, git_path)
     } else {
-        pretty_panic!("Not enough arguments");
-    }
-
-    let mut is_user_input_matched: bool = false;
-    let mut template: Template = Default::default();
-
-    // For loop template folder for templates, when matched, return it
-    for template_file in templates {
-        // You need to construct template_temp struct every time in a loop, because you wanna for loop your existing templates in your fs
-        // If you use template variable, it'll be empty, because it's constructed after templates_temp values are matched with user input and then returned
-        let template_temp = process_template_to_struct(&template_file);
-
-        match matching {
-            Matching::Name => {
-                (is_user_input_matched, template) = match_user_input_with_template_data(
-                    is_user_input_matched,
-                    template_temp.name,
-                    &name,
-                    template_file,
-                    template,
-                );
-            }
-            Matching::Path => {
-                (is_user_input_matched, template) = match_user_input_with_template_data(
-                    is_user_input_matched,
-                    template_temp.path,
-                    &path,
-                    template_file,
-                    template,
-                );
-            }
-            Matching::GitPath => {
-                (is_user_input_matched, template) = match_user_input_with_template_data(
-                    is_user_input_matched,
-                    template_temp.git_path,
-                    &git_path,
-                    template_file,
-                    template,
-                );
+        return Err(DmanError::InvalidInput("Not enough arguments".to_string()));
+    };
+
+    let mut matched_templates: Vec<Template> = get_templates_to_vec()?
+        .into_iter()
+        .filter(|template| {
+            let template_data = match matching {
+                Matching::Name => &template.name,
+                Matching::Path => &template.path,
+                Matching::GitPath => &template.git_path,
+            };
+
+            *template_data == user_input
+        })
+        .collect();
+
+    match matched_templates.len() {
+        0 => Err(DmanError::NotFound(format!("Template {user_input:?}"))),
+        1 => {
+            let template = matched_templates.remove(0);
+            println!("{} template found", template.name);
+
+            #[cfg(debug_assertions)]
+            {
+                println!("Returning template: ");
+                dbg!(&template);
             }
-        }
-    }
-    if !is_user_input_matched {
-        pretty_panic!("Not found");
-    } else {
-        #[cfg(debug_assertions)]
-        {
-            println!("Returning template: ");
-            dbg!(&template);
-        }
 
-        template
+            Ok(template)
+        }
+        _ => Err(DmanError::AmbiguousMatch(
+            matched_templates
+                .into_iter()
+                .map(|template| template.name)
+                .collect(),
+        )),
     }
 }
 
-/// Match user input with existing templates to find one to Git pull
+/// Get names of local branches in repository
+///
+/// # Errors
+///
+/// * If path isn't a Git repository
 #[allow(dead_code)]
-fn match_user_input_with_template_data(
-    previous_value: bool,
-    template_data: String,
-    user_input: &Option<String>,
-    template_file: Result<fs::DirEntry, std::io::Error>,
-    previous_matched_template: Template,
-) -> (bool, Template) {
-    let user_input = user_input.clone().unwrap();
-
-    // Construct template according to data and return it
-    let template = process_template_to_struct(&template_file);
-
-    if template_data == user_input {
-        println!("{} template found", template.name);
-
-        (true, template)
-    } else {
-        (previous_value, previous_matched_template)
+pub fn get_branches(path: String) -> Result<Vec<String>, DmanError> {
+    let path = Path::new(&path);
+    let repo = git2::Repository::open(path)?;
+    let mut branches = Vec::new();
+
+    for branch in repo.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        if let Some(name) = branch.name()? {
+            branches.push(name.to_string());
+        }
     }
+
+    Ok(branches)
 }
 
-/// Check if remote exists
-///
-/// # Arguments
-///
-/// * remote: String
-///
-/// # Panics
-///
-/// * If remote doesn't exist
-///
-/// # Example
-///
-/// ```
-/// use dotfile_manager::check_if_remote_exists;
+/// Read templates from filesystem and put them to Vector
 ///
-/// check_if_remote_exists(String::from("https://github.com/Adamekka/dotfile-manager.git"));
-/// ```
+/// Templates, that can't be parsed, are skipped with a warning
 ///
-/// # Debug
+/// # Errors
 ///
-/// * Print Git path if debug_assertions is set
-#[allow(dead_code)]
-pub fn check_if_remote_exists(remote: String) {
-    let repo = git2::Repository::open(get_fake_git_folder()).unwrap();
-    let mut remote = repo
-        .find_remote(&remote)
-        .or_else(|_| repo.remote_anonymous(&remote))
-        .unwrap();
-    let connection = remote
-        .connect_auth(git2::Direction::Fetch, None, None)
-        .unwrap();
-    let mut remote_origin_exists = false;
-    #[allow(unused_variables)]
-    for head in connection.list().unwrap().iter() {
-        #[cfg(debug_assertions)]
-        println!("{}\t{}", head.oid(), head.name());
-        remote_origin_exists = true;
-    }
+/// * If template folder can't be read
+pub fn get_templates_to_vec() -> Result<Vec<Template>, DmanError> {
+    let templates = get_existing_templates()?;
+    let mut templates_vec: Vec<Template> = Vec::new();
 
-    if remote_origin_exists {
-        println!("Remote origin exists");
-    } else {
-        println!("Remote origin doesn't exist");
+    for template in templates {
+        match process_template_to_struct(&template) {
+            Ok(template) => templates_vec.push(template),
+            Err(e) => warn!("Skipping template: {e}"),
+        }
     }
+
+    Ok(templates_vec)
 }
 
-#[allow(dead_code)]
-pub fn get_branches(path: String) -> Vec<String> {
-    let path = Path::new(&path);
-    let repo = git2::Repository::open(path).expect("Couldn't open repo, bad path maybe?");
-    let mut branches = Vec::new();
+#[cfg(test)]
+mod tests {
+    use super::{Machine, Template};
 
-    for branch in repo.branches(Some(git2::BranchType::Local)).unwrap() {
-        let (branch, _) = branch.unwrap();
-        let name = branch.name().unwrap().unwrap();
-        branches.push(name.to_string());
+    fn template(hosts: &[&str], machine_tags: &[&str]) -> Template {
+        Template {
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            machine_tags: machine_tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
     }
 
-    branches
-}
+    fn machine() -> Machine {
+        Machine {
+            hostname: "work-laptop".to_string(),
+            tags: vec!["laptop".to_string()],
+            ..Default::default()
+        }
+    }
 
-#[allow(dead_code)]
-/// Read templates from filesystem and put them to Vector
-pub fn get_templates_to_vec() -> Vec<Template> {
-    let templates = get_existing_templates();
-    let mut templates_vec: Vec<Template> = Vec::new();
+    #[test]
+    fn template_without_hosts_and_tags_applies_everywhere() {
+        assert!(template(&[], &[]).applies_to(&machine()));
+    }
 
-    for template in templates {
-        let template = process_template_to_struct(&template);
-        templates_vec.push(template);
+    #[test]
+    fn template_applies_to_matching_host() {
+        assert!(template(&["work-*"], &[]).applies_to(&machine()));
+        assert!(template(&["desktop", "work-laptop"], &[]).applies_to(&machine()));
+        assert!(!template(&["desktop"], &[]).applies_to(&machine()));
     }
 
-    dbg!(&templates_vec);
+    #[test]
+    fn template_applies_to_machine_with_tag() {
+        assert!(template(&[], &["server", "laptop"]).applies_to(&machine()));
+        assert!(!template(&[], &["server"]).applies_to(&machine()));
+    }
 
-    templates_vec
+    #[test]
+    fn host_or_tag_is_enough() {
+        assert!(template(&["desktop"], &["laptop"]).applies_to(&machine()));
+        assert!(template(&["work-*"], &["server"]).applies_to(&machine()));
+        assert!(!template(&["desktop"], &["server"]).applies_to(&machine()));
+    }
 }
@@ -12,10 +12,10 @@
  * <http://creativecommons.org/publicdomain/zero/1.0/>.
  */
 
-use crate::lib::check_if_remote_exists;
+use crate::lib::{check_if_remote_exists, credentials::remote_callbacks, is_offline, DmanError};
 use git2::{
     build::{CheckoutBuilder, RepoBuilder},
-    FetchOptions, Progress, RemoteCallbacks,
+    FetchOptions, Progress,
 };
 use mytools::question_yes_no;
 use std::{
@@ -33,7 +33,10 @@ struct State {
 }
 
 fn print(state: &mut State) {
-    let stats = state.progress.as_ref().unwrap();
+    // Local clones report checkout without transfer progress
+    let Some(stats) = state.progress.as_ref() else {
+        return;
+    };
     let network_pct = (100 * stats.received_objects()) / stats.total_objects();
     let index_pct = (100 * stats.indexed_objects()) / stats.total_objects();
     let co_pct = if state.total > 0 {
@@ -76,23 +79,20 @@ fn print(state: &mut State) {
     io::stdout().flush().unwrap();
 }
 
-pub fn run(git_path: &str, path: &Path) -> Result<(), git2::Error> {
+pub fn run(git_path: &str, path: &Path) -> Result<(), DmanError> {
+    if is_offline() {
+        return Err(DmanError::Offline(format!("Cloning {git_path}")));
+    }
+
     // Create folder if not exists
     if !path.exists() {
         question_yes_no!("Folder does not exist. Create it?");
 
-        match std::fs::create_dir_all(path) {
-            Ok(_) => {
-                println!("Folder created: {path:?}");
-            }
-            Err(e) => {
-                println!("Error: {e}");
-                return Err(git2::Error::from_str("Error creating folder"));
-            }
-        }
+        std::fs::create_dir_all(path)?;
+        println!("Folder created: {path:?}");
     }
 
-    check_if_remote_exists(git_path.to_string());
+    check_if_remote_exists(git_path.to_string())?;
 
     let state = RefCell::new(State {
         progress: None,
@@ -101,7 +101,7 @@ pub fn run(git_path: &str, path: &Path) -> Result<(), git2::Error> {
         path: None,
         newline: false,
     });
-    let mut cb = RemoteCallbacks::new();
+    let mut cb = remote_callbacks();
     cb.transfer_progress(|stats| {
         let mut state = state.borrow_mut();
         state.progress = Some(stats.to_owned());
@@ -128,6 +128,6 @@ pub fn run(git_path: &str, path: &Path) -> Result<(), git2::Error> {
 
     match result {
         Ok(_) => Ok(()),
-        Err(e) => Err(e),
+        Err(e) => Err(DmanError::Git(e)),
     }
 }
@@ -12,54 +12,78 @@
  * <http://creativecommons.org/publicdomain/zero/1.0/>.
  */
 
-use git2::Repository;
+use crate::args::backup::snapshot;
+use crate::lib::{credentials::remote_callbacks, PullStrategy, PROMPT_LOCK};
+use git2::{build::CheckoutBuilder, RebaseOptions, Repository, Signature, StatusOptions};
+use mytools::warn;
+use question::{Answer, Question};
 use std::{
+    fmt,
     io::{self, Write},
-    path::Path,
-    str,
+    path::{Path, PathBuf},
+    str::{self, FromStr},
+    sync::PoisonError,
 };
 
+/// Print pull progress, unless pull is quiet
+macro_rules! progress {
+    ($quiet:expr, $($arg:tt)*) => {
+        if !$quiet {
+            println!($($arg)*);
+        }
+    };
+}
+
 fn do_fetch<'a>(
     repo: &'a git2::Repository,
     refs: &[&str],
     remote: &'a mut git2::Remote,
+    quiet: bool,
 ) -> Result<git2::AnnotatedCommit<'a>, git2::Error> {
-    let mut cb = git2::RemoteCallbacks::new();
+    let mut cb = remote_callbacks();
 
     // Print out our transfer progress.
-    cb.transfer_progress(|stats| {
-        if stats.received_objects() == stats.total_objects() {
-            print!(
-                "Resolving deltas {}/{}\r",
-                stats.indexed_deltas(),
-                stats.total_deltas()
-            );
-        } else if stats.total_objects() > 0 {
-            print!(
-                "Received {}/{} objects ({}) in {} bytes\r",
-                stats.received_objects(),
-                stats.total_objects(),
-                stats.indexed_objects(),
-                stats.received_bytes()
-            );
-        }
-        io::stdout().flush().unwrap();
-        true
-    });
+    // Progress of templates pulled in parallel would overwrite each other
+    if !quiet {
+        cb.transfer_progress(|stats| {
+            if stats.received_objects() == stats.total_objects() {
+                print!(
+                    "Resolving deltas {}/{}\r",
+                    stats.indexed_deltas(),
+                    stats.total_deltas()
+                );
+            } else if stats.total_objects() > 0 {
+                print!(
+                    "Received {}/{} objects ({}) in {} bytes\r",
+                    stats.received_objects(),
+                    stats.total_objects(),
+                    stats.indexed_objects(),
+                    stats.received_bytes()
+                );
+            }
+            io::stdout().flush().unwrap();
+            true
+        });
+    }
 
     let mut fo = git2::FetchOptions::new();
     fo.remote_callbacks(cb);
     // Always fetch all tags.
     // Perform a download and also update tips
     fo.download_tags(git2::AutotagOption::All);
-    println!("Fetching {} for repo", remote.name().unwrap());
+    progress!(
+        quiet,
+        "Fetching {} for repo",
+        remote.name().unwrap_or("remote")
+    );
     remote.fetch(refs, Some(&mut fo), None)?;
 
     // If there are local objects (we got a thin pack), then tell the user
     // how many objects we saved from having to cross the network.
     let stats = remote.stats();
     if stats.local_objects() > 0 {
-        println!(
+        progress!(
+            quiet,
             "\rReceived {}/{} objects in {} bytes (used {} local \
               objects)",
             stats.indexed_objects(),
@@ -68,7 +92,8 @@ fn do_fetch<'a>(
             stats.local_objects()
         );
     } else {
-        println!(
+        progress!(
+            quiet,
             "\rReceived {}/{} objects in {} bytes",
             stats.indexed_objects(),
             stats.total_objects(),
@@ -80,47 +105,263 @@ fn do_fetch<'a>(
     repo.reference_to_annotated_commit(&fetch_head)
 }
 
+/// What to do with uncommitted changes, when pull has to update working tree
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirtyAction {
+    /// Keep local changes and skip pulling the template
+    Abort,
+    /// Stash local changes, pull and reapply them
+    Stash,
+    /// Overwrite local changes
+    Force,
+}
+
+impl FromStr for DirtyAction {
+    type Err = git2::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "abort" | "a" => Ok(DirtyAction::Abort),
+            "stash" | "s" => Ok(DirtyAction::Stash),
+            "force" | "f" => Ok(DirtyAction::Force),
+            _ => Err(git2::Error::from_str(&format!(
+                "Unknown action {s:?}, use abort, stash or force"
+            ))),
+        }
+    }
+}
+
+/// Options of a single pull
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PullOptions {
+    /// What happens with uncommitted changes, user is asked if it's None
+    pub on_dirty: Option<DirtyAction>,
+    /// How to integrate remote changes, when branches diverged
+    pub strategy: PullStrategy,
+    /// Don't print progress, used when templates are pulled in parallel
+    pub quiet: bool,
+}
+
+/// What pull did with template
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PullOutcome {
+    /// Nothing new on remote
+    UpToDate,
+    /// Branch was moved to remote commit
+    FastForward,
+    /// Remote changes were merged into local branch
+    Merge,
+    /// Local commits were replayed on top of remote branch
+    Rebase,
+}
+
+impl fmt::Display for PullOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PullOutcome::UpToDate => write!(f, "up to date"),
+            PullOutcome::FastForward => write!(f, "fast-forward"),
+            PullOutcome::Merge => write!(f, "merge"),
+            PullOutcome::Rebase => write!(f, "rebase"),
+        }
+    }
+}
+
+/// Local changes after they were taken care of
+enum LocalChanges {
+    /// Working tree was clean
+    None,
+    /// Changes were stashed, second handle is used, because stashing needs `&mut Repository`
+    Stashed(Repository),
+    /// Changes are going to be overwritten
+    Overwritten,
+}
+
+impl LocalChanges {
+    /// Checkout options that won't destroy local changes, unless user asked for it
+    fn checkout_builder(&self) -> CheckoutBuilder<'static> {
+        let mut checkout = CheckoutBuilder::new();
+
+        match self {
+            LocalChanges::Overwritten => checkout.force(),
+            _ => checkout.safe(),
+        };
+
+        checkout
+    }
+
+    /// Reapply stashed changes
+    fn restore(self, quiet: bool) -> Result<(), git2::Error> {
+        if let LocalChanges::Stashed(mut repo) = self {
+            progress!(quiet, "Reapplying stashed changes");
+            // Index was changed by checkout through the other handle
+            repo.index()?.read(true)?;
+            repo.stash_pop(0, None).map_err(|e| {
+                git2::Error::from_str(&format!(
+                    "Couldn't reapply stashed changes, they're kept in git stash: {}",
+                    e.message()
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check if tracked files have uncommitted changes
+fn has_local_changes(repo: &Repository) -> Result<bool, git2::Error> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+
+    Ok(!repo.statuses(Some(&mut options))?.is_empty())
+}
+
+/// Back up changed and untracked files, before they're overwritten by force checkout
+fn back_up_local_changes(repo: &Repository) -> Result<Option<String>, git2::Error> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(None);
+    };
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+
+    let files: Vec<PathBuf> = repo
+        .statuses(Some(&mut options))?
+        .iter()
+        .filter_map(|entry| entry.path().map(|path| workdir.join(path)))
+        .collect();
+    let label = workdir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    snapshot(&label, "pull --on-dirty force", &files).map_err(|e| {
+        git2::Error::from_str(&format!(
+            "Couldn't back up local changes, pull aborted: {e}"
+        ))
+    })
+}
+
+/// Ask user what to do with uncommitted changes, abort is the default
+fn ask_dirty_action(repo: &Repository) -> DirtyAction {
+    let _prompt = PROMPT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let path = repo.workdir().unwrap_or_else(|| repo.path());
+    warn!("{path:?} has uncommitted changes");
+
+    let answer = Question::new("Abort pull, stash and reapply changes, or force overwrite them?")
+        .acceptable(vec!["abort", "stash", "force", "a", "s", "f"])
+        .until_acceptable()
+        .default(Answer::RESPONSE("abort".to_string()))
+        .show_defaults()
+        .ask();
+
+    match answer {
+        Some(Answer::RESPONSE(action)) => action.parse().unwrap_or(DirtyAction::Abort),
+        _ => DirtyAction::Abort,
+    }
+}
+
+/// Make sure uncommitted changes aren't lost by updating working tree
+fn protect_local_changes(
+    repo: &Repository,
+    on_dirty: &mut Option<DirtyAction>,
+    quiet: bool,
+) -> Result<LocalChanges, git2::Error> {
+    if !has_local_changes(repo)? {
+        return Ok(LocalChanges::None);
+    }
+
+    // Ask only once per repository
+    let action = *on_dirty.get_or_insert_with(|| ask_dirty_action(repo));
+
+    match action {
+        DirtyAction::Abort => Err(git2::Error::from_str(
+            "Working tree has uncommitted changes, pull aborted",
+        )),
+        DirtyAction::Stash => {
+            progress!(quiet, "Stashing local changes");
+            let mut stash_repo = Repository::open(repo.path())?;
+            let signature = stash_repo
+                .signature()
+                .or_else(|_| Signature::now("dotfile-manager", "dotfile-manager@localhost"))?;
+            stash_repo.stash_save(&signature, "dman: autostash before pull", None)?;
+
+            Ok(LocalChanges::Stashed(stash_repo))
+        }
+        DirtyAction::Force => {
+            match back_up_local_changes(repo)? {
+                Some(id) => {
+                    warn!("Overwriting local changes, restore them with `dman backup restore {id}`")
+                }
+                None => warn!("Overwriting local changes"),
+            }
+
+            Ok(LocalChanges::Overwritten)
+        }
+    }
+}
+
+/// Fast-forward checked out branch, working tree is updated with it
 fn fast_forward(
     repo: &Repository,
     lb: &mut git2::Reference,
     rc: &git2::AnnotatedCommit,
+    checkout: &mut CheckoutBuilder,
+    quiet: bool,
 ) -> Result<(), git2::Error> {
     let name = match lb.name() {
         Some(s) => s.to_string(),
         None => String::from_utf8_lossy(lb.name_bytes()).to_string(),
     };
     let msg = format!("Fast-Forward: Setting {} to id: {}", name, rc.id());
-    println!("{msg}");
+    progress!(quiet, "{msg}");
+    // Update working tree before moving the branch, checkout compares files with current HEAD
+    // to find out which of them are safe to update
+    let target = repo.find_object(rc.id(), None)?;
+    repo.checkout_tree(&target, Some(checkout))?;
     lb.set_target(rc.id(), &msg)?;
     repo.set_head(&name)?;
-    repo.checkout_head(Some(
-        git2::build::CheckoutBuilder::default()
-            // For some reason the force is required to make the working directory actually get updated
-            // I suspect we should be adding some logic to handle dirty working directory states
-            // but this is just an example so maybe not.
-            .force(),
-    ))?;
     Ok(())
 }
 
+/// Get paths of files with merge conflicts
+pub fn conflicted_files(index: &git2::Index) -> Result<Vec<String>, git2::Error> {
+    let mut files = Vec::new();
+
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        // Every side can be missing, e.g. when file was deleted on one of them
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            files.push(String::from_utf8_lossy(&entry.path).to_string());
+        }
+    }
+
+    Ok(files)
+}
+
 fn normal_merge(
     repo: &Repository,
     local: &git2::AnnotatedCommit,
     remote: &git2::AnnotatedCommit,
+    checkout: &mut CheckoutBuilder,
 ) -> Result<(), git2::Error> {
-    let local_tree = repo.find_commit(local.id())?.tree()?;
-    let remote_tree = repo.find_commit(remote.id())?.tree()?;
-    let ancestor = repo
-        .find_commit(repo.merge_base(local.id(), remote.id())?)?
-        .tree()?;
-    let mut idx = repo.merge_trees(&ancestor, &local_tree, &remote_tree, None)?;
+    // Merge into working tree, conflicts are written as conflict markers into files
+    // and merge state is kept, so it can be finished after conflicts are resolved
+    checkout.allow_conflicts(true).conflict_style_merge(true);
+    repo.merge(&[remote], None, Some(checkout))?;
 
+    let mut idx = repo.index()?;
     if idx.has_conflicts() {
-        println!("Merge conflicts detected...");
-        repo.checkout_index(Some(&mut idx), None)?;
-        return Ok(());
+        // ErrorCode::MergeConflict can't be used, git2 turns it into GenericError
+        return Err(git2::Error::new(
+            git2::ErrorCode::Conflict,
+            git2::ErrorClass::Merge,
+            format!("Merge conflicts in: {}", conflicted_files(&idx)?.join(", ")),
+        ));
     }
-    let result_tree = repo.find_tree(idx.write_tree_to(repo)?)?;
+    let result_tree = repo.find_tree(idx.write_tree()?)?;
     // now create the merge commit
     let msg = format!("Merge: {} into {}", remote.id(), local.id());
     let sig = repo.signature()?;
@@ -135,8 +376,61 @@ fn normal_merge(
         &result_tree,
         &[&local_commit, &remote_commit],
     )?;
-    // Set working tree to match head.
-    repo.checkout_head(None)?;
+    // Merge is done, remove MERGE_HEAD and other merge state
+    repo.cleanup_state()?;
+    Ok(())
+}
+
+/// Replay local commits on top of fetched commit
+///
+/// Rebase is aborted on conflicts, so repository is left as it was before
+fn rebase(
+    repo: &Repository,
+    local: &git2::AnnotatedCommit,
+    remote: &git2::AnnotatedCommit,
+    checkout: CheckoutBuilder,
+    quiet: bool,
+) -> Result<(), git2::Error> {
+    let mut options = RebaseOptions::new();
+    options.checkout_options(checkout);
+
+    let sig = repo.signature()?;
+    let mut rebase = repo.rebase(Some(local), Some(remote), None, Some(&mut options))?;
+
+    while let Some(operation) = rebase.next() {
+        if let Err(e) = operation {
+            rebase.abort()?;
+            return Err(e);
+        }
+
+        let index = repo.index()?;
+        if index.has_conflicts() {
+            let files = conflicted_files(&index)?;
+            rebase.abort()?;
+            return Err(git2::Error::new(
+                git2::ErrorCode::Conflict,
+                git2::ErrorClass::Rebase,
+                format!(
+                    "Rebase conflicts in: {}, rebase was aborted, use merge strategy to resolve them",
+                    files.join(", ")
+                ),
+            ));
+        }
+
+        match rebase.commit(None, &sig, None) {
+            Ok(_) => {}
+            // Local commit is already in remote branch, skip it
+            Err(e) if e.code() == git2::ErrorCode::Applied => {}
+            Err(e) => {
+                rebase.abort()?;
+                return Err(e);
+            }
+        }
+    }
+
+    rebase.finish(Some(&sig))?;
+    progress!(quiet, "Rebased local commits on top of {}", remote.id());
+
     Ok(())
 }
 
@@ -144,58 +438,142 @@ fn do_merge<'a>(
     repo: &'a Repository,
     remote_branch: &str,
     fetch_commit: git2::AnnotatedCommit<'a>,
-) -> Result<(), git2::Error> {
-    // 1. do a merge analysis
-    let analysis = repo.merge_analysis(&[&fetch_commit])?;
+    on_dirty: &mut Option<DirtyAction>,
+    strategy: PullStrategy,
+    quiet: bool,
+) -> Result<PullOutcome, git2::Error> {
+    let refname = format!("refs/heads/{remote_branch}");
+    // HEAD points to the branch even if it has no commits yet
+    let is_head = repo
+        .find_reference("HEAD")
+        .is_ok_and(|head| head.symbolic_target() == Some(refname.as_str()));
+    let branch = repo.find_reference(&refname).ok();
+    let msg = format!(
+        "Fast-Forward: Setting {refname} to id: {}",
+        fetch_commit.id()
+    );
+
+    // Missing branch, that isn't checked out, is just created
+    if branch.is_none() && !is_head {
+        progress!(quiet, "{msg}");
+        repo.reference(&refname, fetch_commit.id(), false, &msg)?;
+        return Ok(PullOutcome::FastForward);
+    }
 
-    // 2. Do the appropriate merge
-    if analysis.0.is_fast_forward() {
-        println!("Doing a fast forward");
+    // 1. do a merge analysis, against the branch, that can be other than HEAD
+    let analysis = match &branch {
+        Some(branch) => repo.merge_analysis_for_ref(branch, &[&fetch_commit])?,
+        None => repo.merge_analysis(&[&fetch_commit])?,
+    };
+
+    if analysis.0.is_up_to_date() || !(analysis.0.is_fast_forward() || analysis.0.is_normal()) {
+        progress!(quiet, "Nothing to do...");
+        return Ok(PullOutcome::UpToDate);
+    }
+
+    // Fast-forward is reported as normal merge too
+    if analysis.0.is_normal() && !analysis.0.is_fast_forward() {
+        if strategy == PullStrategy::FfOnly {
+            return Err(git2::Error::from_str(&format!(
+                "{remote_branch} diverged from remote and can't be fast-forwarded, \
+                 pull with merge or rebase strategy"
+            )));
+        }
+
+        // Merge and rebase work with HEAD, so only checked out branch can be integrated
+        if !is_head {
+            return Err(git2::Error::from_str(&format!(
+                "{remote_branch} diverged from remote, checkout it to pull it"
+            )));
+        }
+    }
+
+    // Branch, that isn't checked out, has nothing in working tree, only the branch is moved
+    if !is_head {
+        if let Some(mut branch) = branch {
+            progress!(quiet, "{msg}");
+            branch.set_target(fetch_commit.id(), &msg)?;
+        }
+        return Ok(PullOutcome::FastForward);
+    }
+
+    // 2. Working tree is going to change, take care of uncommitted changes first
+    let local_changes = protect_local_changes(repo, on_dirty, quiet)?;
+    let mut checkout = local_changes.checkout_builder();
+
+    // 3. Do the appropriate merge
+    let result = if analysis.0.is_fast_forward() {
+        progress!(quiet, "Doing a fast forward");
         // do a fast forward
-        let refname = format!("refs/heads/{remote_branch}");
-        match repo.find_reference(&refname) {
-            Ok(mut r) => {
-                fast_forward(repo, &mut r, &fetch_commit)?;
-            }
-            Err(_) => {
+        let result = match branch {
+            Some(mut r) => fast_forward(repo, &mut r, &fetch_commit, &mut checkout, quiet),
+            None => {
                 // The branch doesn't exist so just set the reference to the
                 // commit directly. Usually this is because you are pulling
                 // into an empty repository.
-                repo.reference(
-                    &refname,
-                    fetch_commit.id(),
-                    true,
-                    &format!("Setting {} to {}", remote_branch, fetch_commit.id()),
-                )?;
-                repo.set_head(&refname)?;
-                repo.checkout_head(Some(
-                    git2::build::CheckoutBuilder::default()
-                        .allow_conflicts(true)
-                        .conflict_style_merge(true)
-                        .force(),
-                ))?;
+                repo.find_object(fetch_commit.id(), None)
+                    .and_then(|target| repo.checkout_tree(&target, Some(&mut checkout)))
+                    .and_then(|_| {
+                        repo.reference(
+                            &refname,
+                            fetch_commit.id(),
+                            true,
+                            &format!("Setting {} to {}", remote_branch, fetch_commit.id()),
+                        )
+                    })
+                    .and_then(|_| repo.set_head(&refname))
             }
         };
-    } else if analysis.0.is_normal() {
-        // do a normal merge
-        let head_commit = repo.reference_to_annotated_commit(&repo.head()?)?;
-        normal_merge(repo, &head_commit, &fetch_commit)?;
+
+        result.map(|_| PullOutcome::FastForward)
     } else {
-        println!("Nothing to do...");
+        let head_commit = repo
+            .head()
+            .and_then(|head| repo.reference_to_annotated_commit(&head));
+
+        match (strategy, head_commit) {
+            (PullStrategy::Rebase, Ok(head_commit)) => {
+                progress!(quiet, "Doing a rebase");
+                rebase(repo, &head_commit, &fetch_commit, checkout, quiet)
+                    .map(|_| PullOutcome::Rebase)
+            }
+            (_, Ok(head_commit)) => {
+                // do a normal merge
+                normal_merge(repo, &head_commit, &fetch_commit, &mut checkout)
+                    .map(|_| PullOutcome::Merge)
+            }
+            (_, Err(e)) => Err(e),
+        }
+    };
+
+    // 4. Reapply stashed changes, even if pull failed
+    let restored = local_changes.restore(quiet);
+
+    // Pull error is returned, so tell user about stash separately
+    if let (Err(_), Err(e)) = (&result, &restored) {
+        warn!("{}", e.message());
     }
-    Ok(())
-}
 
-// TODO: handle private repos
+    let outcome = result?;
+    restored?;
+
+    Ok(outcome)
+}
 
 /// Pull changes from Git repository
-pub fn run(path: String, remote_branches: Vec<String>) -> Result<(), git2::Error> {
-    let remote_name = "origin";
+///
+/// Returns the most significant outcome of pulled branches
+pub fn run(
+    path: String,
+    remote_name: &str,
+    remote_branches: Vec<String>,
+    options: PullOptions,
+) -> Result<PullOutcome, git2::Error> {
+    let mut on_dirty = options.on_dirty;
     let path = Path::new(&path);
     let repo = Repository::open(path)?;
     let mut remote = repo.find_remote(remote_name)?;
-    let mut results: Vec<Result<(), git2::Error>> = Vec::new();
-    let mut final_result: Result<(), git2::Error> = Err(git2::Error::from_str("No branches found"));
+    let mut results: Vec<Result<PullOutcome, git2::Error>> = Vec::new();
 
     for remote_branch in remote_branches {
         #[cfg(debug_assertions)]
@@ -203,8 +581,15 @@ pub fn run(path: String, remote_branches: Vec<String>) -> Result<(), git2::Error
             println!("Pulling config from git repo");
             println!("path: {path:?}, remote branch: {remote_branch}");
         }
-        let fetch_commit = do_fetch(&repo, &[&remote_branch], &mut remote)?;
-        results.push(do_merge(&repo, &remote_branch, fetch_commit));
+        let fetch_commit = do_fetch(&repo, &[&remote_branch], &mut remote, options.quiet)?;
+        results.push(do_merge(
+            &repo,
+            &remote_branch,
+            fetch_commit,
+            &mut on_dirty,
+            options.strategy,
+            options.quiet,
+        ));
     }
 
     // Check if at least 1 pull was successful
@@ -212,14 +597,13 @@ pub fn run(path: String, remote_branches: Vec<String>) -> Result<(), git2::Error
         return Err(git2::Error::from_str("No branches found"));
     }
 
-    println!("{results:?}");
-
+    // Every branch has to be pulled, otherwise return first error
+    let mut outcome = PullOutcome::UpToDate;
     for result in results {
-        if result.is_ok() {
-            println!("Pull successful");
-            final_result = Ok(());
-        }
+        outcome = outcome.max(result?);
     }
 
-    final_result
+    progress!(options.quiet, "Pull successful");
+
+    Ok(outcome)
 }
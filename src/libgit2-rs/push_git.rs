@@ -0,0 +1,178 @@
+use crate::lib::credentials::remote_callbacks;
+use git2::{IndexAddOption, PushOptions, Repository};
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Stage every change in the working tree, including deleted files
+///
+/// Returns id of the tree written from the index
+fn stage_all(repo: &Repository) -> Result<git2::Oid, git2::Error> {
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    index.update_all(["*"].iter(), None)?;
+    index.write()?;
+    index.write_tree()
+}
+
+/// Get paths of files that differ between HEAD and the index
+fn staged_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree()?),
+        // Empty repository, everything in index is new
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, None)?;
+
+    let files = diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+
+    Ok(files)
+}
+
+/// Create commit message from list of changed files
+fn generate_message(files: &[String]) -> String {
+    let summary = match files {
+        [file] => format!("Update {file}"),
+        _ => format!("Update {} files", files.len()),
+    };
+
+    let body: Vec<String> = files.iter().map(|file| format!("- {file}")).collect();
+
+    format!("{summary}\n\n{}", body.join("\n"))
+}
+
+/// Commit staged changes on top of HEAD
+///
+/// Returns None if there is nothing to commit
+fn do_commit(
+    repo: &Repository,
+    tree_id: git2::Oid,
+    message: Option<String>,
+) -> Result<Option<git2::Oid>, git2::Error> {
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e),
+    };
+
+    let files = staged_files(repo)?;
+    if files.is_empty() {
+        println!("Nothing to commit");
+        return Ok(None);
+    }
+
+    let message = message.unwrap_or_else(|| generate_message(&files));
+    let tree = repo.find_tree(tree_id)?;
+    let sig = repo.signature()?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+
+    let commit_id = repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &parents)?;
+    println!("Committed {} file(s) as {commit_id}", files.len());
+
+    Ok(Some(commit_id))
+}
+
+/// Get name of the branch HEAD points to
+fn current_branch(repo: &Repository) -> Result<String, git2::Error> {
+    let head = repo.head()?;
+
+    if !head.is_branch() {
+        return Err(git2::Error::from_str(
+            "HEAD is detached, checkout a branch before pushing",
+        ));
+    }
+
+    match head.shorthand() {
+        Some(branch) => Ok(branch.to_string()),
+        None => Err(git2::Error::from_str("Branch name is not valid UTF-8")),
+    }
+}
+
+fn do_push(repo: &Repository, remote_name: &str, branch: &str) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote_name)?;
+    // Server can reject reference update even when the push itself succeeds
+    let rejected: RefCell<Option<String>> = RefCell::new(None);
+
+    let mut cb = remote_callbacks();
+    cb.push_transfer_progress(|current, total, _bytes| {
+        print!("Writing objects {current}/{total}\r");
+        io::stdout().flush().unwrap();
+    });
+    cb.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            *rejected.borrow_mut() = Some(format!("{refname}: {status}"));
+        }
+        Ok(())
+    });
+
+    let mut po = PushOptions::new();
+    po.remote_callbacks(cb);
+
+    println!("Pushing {branch} to {remote_name}");
+    let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+    remote.push(&[&refspec], Some(&mut po))?;
+    println!();
+
+    // Callbacks borrow `rejected`, so they have to be dropped first
+    drop(po);
+    if let Some(status) = rejected.into_inner() {
+        return Err(git2::Error::from_str(&format!("Push rejected: {status}")));
+    }
+
+    // Track remote branch, so ahead/behind can be computed later
+    let mut local_branch = repo.find_branch(branch, git2::BranchType::Local)?;
+    if local_branch.upstream().is_err() {
+        local_branch.set_upstream(Some(&format!("{remote_name}/{branch}")))?;
+    }
+
+    Ok(())
+}
+
+/// Commit all local changes without pushing
+///
+/// Returns None if there is nothing to commit
+pub fn commit(path: &Path, message: Option<String>) -> Result<Option<git2::Oid>, git2::Error> {
+    let repo = Repository::open(path)?;
+    let tree_id = stage_all(&repo)?;
+
+    do_commit(&repo, tree_id, message)
+}
+
+/// Push current branch to remote without committing
+pub fn push_current_branch(path: &Path, remote_name: &str) -> Result<(), git2::Error> {
+    let repo = Repository::open(path)?;
+    let branch = current_branch(&repo)?;
+    do_push(&repo, remote_name, &branch)?;
+
+    println!("Push successful");
+
+    Ok(())
+}
+
+/// Commit all local changes and push current branch to remote
+pub fn run(path: String, remote_name: &str, message: Option<String>) -> Result<(), git2::Error> {
+    let path = Path::new(&path);
+    let repo = Repository::open(path)?;
+
+    #[cfg(debug_assertions)]
+    {
+        println!("Pushing config to git repo");
+        println!("path: {path:?}");
+    }
+
+    let tree_id = stage_all(&repo)?;
+    do_commit(&repo, tree_id, message)?;
+
+    let branch = current_branch(&repo)?;
+    do_push(&repo, remote_name, &branch)?;
+
+    println!("Push successful");
+
+    Ok(())
+}
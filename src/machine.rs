@@ -0,0 +1,127 @@
+use super::DmanError;
+use mytools::env::get_home_folder;
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+use toml::value::Table;
+
+/// Machine identity file, written by user
+///
+/// ```toml
+/// # Overrides hostname of the system
+/// hostname = "work-laptop"
+/// tags = ["work", "laptop"]
+///
+/// [variables]
+/// email = "me@work.com"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+struct MachineFile {
+    hostname: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    variables: Table,
+}
+
+/// Machine dman is running on
+#[derive(Clone, Debug, Default)]
+pub struct Machine {
+    pub hostname: String,
+    pub tags: Vec<String>,
+    /// Variables for rendering templated files
+    pub variables: Table,
+}
+
+/// Path to ~/.config/dotfile-manager/machine.toml
+pub fn machine_file_path() -> PathBuf {
+    PathBuf::from(get_home_folder()).join(".config/dotfile-manager/machine.toml")
+}
+
+impl Machine {
+    /// Identify current machine by hostname and machine.toml
+    ///
+    /// Missing machine.toml means the machine has no tags
+    ///
+    /// # Errors
+    ///
+    /// * If hostname can't be read
+    /// * If machine.toml can't be read or parsed
+    pub fn current() -> Result<Self, DmanError> {
+        let path = machine_file_path();
+
+        let file = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            toml::from_str(&contents).map_err(|source| DmanError::TomlParse {
+                file: path.to_string_lossy().to_string(),
+                source,
+            })?
+        } else {
+            MachineFile::default()
+        };
+
+        let hostname = match file.hostname {
+            Some(hostname) => hostname,
+            None => hostname::get()?.to_string_lossy().to_string(),
+        };
+
+        Ok(Self {
+            hostname,
+            tags: file.tags,
+            variables: file.variables,
+        })
+    }
+
+    /// Check if hostname matches glob pattern, e.g. `work-*`
+    pub fn matches_host(&self, pattern: &str) -> bool {
+        match glob::Pattern::new(pattern) {
+            Ok(pattern) => pattern.matches(&self.hostname),
+            // Invalid pattern can still match literally
+            Err(_) => pattern == self.hostname,
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|machine_tag| machine_tag == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Machine;
+
+    fn machine(hostname: &str) -> Machine {
+        Machine {
+            hostname: hostname.to_string(),
+            tags: vec!["work".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn host_matches_literally() {
+        assert!(machine("laptop").matches_host("laptop"));
+        assert!(!machine("laptop").matches_host("desktop"));
+        assert!(!machine("laptop").matches_host("lap"));
+    }
+
+    #[test]
+    fn host_matches_glob() {
+        assert!(machine("work-laptop").matches_host("work-*"));
+        assert!(machine("pc1").matches_host("pc?"));
+        assert!(machine("pc2").matches_host("pc[12]"));
+        assert!(!machine("home-laptop").matches_host("work-*"));
+        assert!(!machine("pc3").matches_host("pc[12]"));
+    }
+
+    #[test]
+    fn invalid_pattern_matches_literally() {
+        assert!(machine("a[b").matches_host("a[b"));
+        assert!(!machine("ab").matches_host("a[b"));
+    }
+
+    #[test]
+    fn tags() {
+        assert!(machine("laptop").has_tag("work"));
+        assert!(!machine("laptop").has_tag("home"));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::lib;
+use lib::{
+    get_existing_templates, read_template_file, write_template_file, DmanError, TEMPLATE_VERSION,
+};
+use mytools::warn;
+use std::path::Path;
+
+/// Rewrite template file to current version
+///
+/// Returns false if file is already in current version
+fn migrate_file(path: &Path) -> Result<bool, DmanError> {
+    let (version, template) = read_template_file(path)?;
+
+    if version == TEMPLATE_VERSION {
+        return Ok(false);
+    }
+
+    // Template is written to the file it was read from, even if its name differs
+    write_template_file(path, &template)?;
+
+    Ok(true)
+}
+
+/// Rewrite template files written by older dman to current template version
+///
+/// Comments and formatting of migrated files aren't kept
+///
+/// # Errors
+///
+/// * If template folder can't be read
+/// * If any template file can't be migrated, the rest is migrated anyway
+pub fn migrate_templates() -> Result<(), DmanError> {
+    let mut migrated = 0;
+    let mut failed = 0;
+
+    for template_file in get_existing_templates()? {
+        let path = template_file?.path();
+
+        match migrate_file(&path) {
+            Ok(true) => {
+                println!("Migrated {path:?} to version {TEMPLATE_VERSION}");
+                migrated += 1;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn!("Couldn't migrate {path:?}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{migrated} template(s) migrated");
+
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!(
+            "{failed} template file(s) couldn't be migrated"
+        )));
+    }
+
+    Ok(())
+}
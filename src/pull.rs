@@ -1,39 +1,280 @@
 #[path = "./libgit2-rs/pull_git.rs"]
 pub mod pull_git;
 
+use crate::args::{
+    hooks::{head_commit, run_hook, Commits, Hook},
+    list::style_table,
+    secret::decrypt_secrets,
+};
 use crate::lib;
+use git2::{ErrorClass, ErrorCode, Repository};
 use lib::{
-    get_branches, get_existing_templates, match_user_input_with_existing_templates,
-    process_template_to_struct, Template,
+    expand_home, get_branches, get_existing_templates, is_offline, machine::Machine,
+    match_user_input_with_existing_templates, process_template_to_struct, DmanError, PullStrategy,
+    Template,
+};
+use owo_colors::OwoColorize;
+use pull_git::{DirtyAction, PullOptions, PullOutcome};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
+    thread,
 };
+use tabled::{Table, Tabled};
+
+/// How many templates are pulled at once by default
+pub const DEFAULT_JOBS: u16 = 4;
+
+/// Row of summary table printed after pulling all templates
+#[derive(Tabled)]
+struct PullSummary {
+    name: String,
+    result: String,
+    details: String,
+    #[tabled(skip)]
+    failed: bool,
+}
+
+impl PullSummary {
+    fn new(name: String, result: Result<PullOutcome, DmanError>) -> Self {
+        let (result, details, failed) = match result {
+            Ok(PullOutcome::UpToDate) => ("up to date".green().to_string(), String::new(), false),
+            Ok(outcome) => ("updated".green().to_string(), outcome.to_string(), false),
+            Err(DmanError::MergeConflict { files, .. }) => {
+                ("conflicted".yellow().to_string(), files.join(", "), true)
+            }
+            Err(e @ DmanError::Hook { .. }) => {
+                ("hook failed".yellow().to_string(), e.to_string(), true)
+            }
+            Err(e) => ("failed".red().to_string(), e.to_string(), true),
+        };
 
-pub fn pull(name: Option<String>, path: Option<String>, git_path: Option<String>) {
-    let template = match_user_input_with_existing_templates(name, path, git_path);
+        Self {
+            name,
+            result,
+            details,
+            failed,
+        }
+    }
+
+    /// Row for template, that wasn't pulled
+    fn skipped(name: String, reason: &str) -> Self {
+        Self {
+            name,
+            result: "skipped".dimmed().to_string(),
+            details: reason.to_string(),
+            failed: false,
+        }
+    }
+}
 
-    let branches = get_branches(template.path.clone());
+/// Pull every branch of template, merge conflicts are turned into DmanError::MergeConflict
+///
+/// `strategy` overrides pull strategy of template
+/// pre_pull hook runs before pull, post_pull only if pull changed something
+fn pull_template(
+    template: &Template,
+    on_dirty: Option<DirtyAction>,
+    strategy: Option<PullStrategy>,
+    quiet: bool,
+) -> Result<PullOutcome, DmanError> {
+    let options = PullOptions {
+        on_dirty,
+        strategy: strategy.or(template.pull_strategy).unwrap_or_default(),
+        quiet,
+    };
+
+    let old = head_commit(&template.path);
+    let commits = Commits {
+        old: old.clone(),
+        new: old,
+    };
+    run_hook(template, Hook::PrePull, &commits, quiet)?;
+
+    let path = expand_home(&template.path);
+    let branches = match &template.branch {
+        Some(branch) => vec![branch.clone()],
+        None => get_branches(path.to_string_lossy().to_string())?,
+    };
     // Pass path from matched template to function, that'll pull changes from GitHub
-    let result = pull_git::run(template.path, branches);
-    println!("{result:?}");
+    let result = pull_git::run(
+        path.to_string_lossy().to_string(),
+        template.remote_name(),
+        branches,
+        options,
+    );
+
+    match result {
+        Ok(outcome) => {
+            decrypt_secrets(template)?;
+
+            if outcome != PullOutcome::UpToDate {
+                let commits = Commits {
+                    new: head_commit(&template.path),
+                    ..commits
+                };
+                run_hook(template, Hook::PostPull, &commits, quiet)?;
+            }
+
+            Ok(outcome)
+        }
+        Err(e) if e.code() == ErrorCode::Conflict && e.class() == ErrorClass::Merge => {
+            let repo = Repository::open(&path)?;
+            let files = pull_git::conflicted_files(&repo.index()?)?;
+
+            Err(DmanError::MergeConflict {
+                template: template.name.clone(),
+                files,
+            })
+        }
+        Err(e) => Err(DmanError::Git(e)),
+    }
 }
 
-/// Git pull every template
-pub fn pull_all() {
-    // Put all templates in a vector
-    let mut templates: Vec<Template> = get_existing_templates()
-        .map(|x| process_template_to_struct(&x))
-        .collect();
+/// Git pull template
+///
+/// `on_dirty` decides what happens with uncommitted changes, user is asked if it's None
+/// `strategy` overrides pull strategy set in template
+/// In offline mode template is skipped
+pub fn pull(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    on_dirty: Option<DirtyAction>,
+    strategy: Option<PullStrategy>,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+
+    if is_offline() {
+        println!("{}: {}, dman is offline", template.name, "skipped".dimmed());
+        return Ok(());
+    }
+
+    pull_template(&template, on_dirty, strategy, false)?;
+
+    Ok(())
+}
+
+/// Git pull every template, `jobs` templates are pulled at once
+///
+/// `on_dirty` decides what happens with uncommitted changes, user is asked for every
+/// dirty template if it's None, one template at a time
+/// `strategy` overrides pull strategy set in templates
+///
+/// Templates for other machines are pulled only if `all_hosts` is true,
+/// in offline mode every template is skipped
+///
+/// Summary of all templates is printed at the end,
+/// error is returned if any template failed or has conflicts
+pub fn pull_all(
+    on_dirty: Option<DirtyAction>,
+    strategy: Option<PullStrategy>,
+    jobs: usize,
+    all_hosts: bool,
+) -> Result<(), DmanError> {
+    let machine = Machine::current()?;
+    let mut templates: Vec<Template> = Vec::new();
+    let mut summary: Vec<PullSummary> = Vec::new();
+
+    // Malformed template shouldn't stop pulling the rest
+    for template_file in get_existing_templates()? {
+        match process_template_to_struct(&template_file) {
+            Ok(template) => templates.push(template),
+            Err(e) => {
+                let name = match &template_file {
+                    Ok(entry) => entry.file_name().to_string_lossy().to_string(),
+                    Err(_) => "?".to_string(),
+                };
+                summary.push(PullSummary::new(name, Err(e)));
+            }
+        }
+    }
+
+    // Disabled templates, templates of other machines and all templates in offline mode
+    // are listed in summary, but not pulled
+    templates.retain(|template| {
+        let reason = if !template.enabled {
+            "disabled"
+        } else if !all_hosts && !template.applies_to(&machine) {
+            "other machine"
+        } else if is_offline() {
+            "offline"
+        } else {
+            return true;
+        };
+
+        summary.push(PullSummary::skipped(template.name.clone(), reason));
+        false
+    });
 
     // Sort templates by name alphabetically
     templates.sort_by(|a, b| a.name.cmp(&b.name));
 
-    for template in templates {
-        println!("Pulling changes for: {}", template.name);
-        #[cfg(debug_assertions)]
-        {
-            println!("{template:?}");
+    let total = templates.len();
+    let jobs = jobs.clamp(1, total.max(1));
+    // Output of parallel pulls would be mixed together, so only results are printed
+    let quiet = jobs > 1;
+
+    let queue = Mutex::new(templates.into_iter());
+    let pulled: Mutex<Vec<PullSummary>> = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                let Some(template) = next else {
+                    break;
+                };
+
+                if !quiet {
+                    println!("Pulling changes for: {}", template.name);
+                }
+
+                // Panic in one template shouldn't take down the others
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    pull_template(&template, on_dirty, strategy, quiet)
+                }))
+                .unwrap_or_else(|_| {
+                    Err(DmanError::InvalidInput(
+                        "Pull panicked, see message above".to_string(),
+                    ))
+                });
+
+                let row = PullSummary::new(template.name, result);
+                let mut pulled = pulled.lock().unwrap_or_else(PoisonError::into_inner);
+                println!(
+                    "[{}/{total}] {}: {}",
+                    pulled.len() + 1,
+                    row.name,
+                    row.result
+                );
+                pulled.push(row);
+            });
         }
-        let branches = get_branches(template.path.clone());
-        let result = pull_git::run(template.path, branches);
-        println!("{result:?}");
+    });
+
+    summary.extend(pulled.into_inner().unwrap_or_else(PoisonError::into_inner));
+
+    if summary.is_empty() {
+        println!("{}", "No templates found".red());
+        return Ok(());
+    }
+
+    summary.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut table = Table::new(&summary);
+    style_table(&mut table);
+    println!("{table}");
+
+    let failed: Vec<String> = summary
+        .into_iter()
+        .filter(|row| row.failed)
+        .map(|row| row.name)
+        .collect();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(DmanError::PullFailed(failed))
     }
 }
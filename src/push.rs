@@ -0,0 +1,52 @@
+#[path = "./libgit2-rs/push_git.rs"]
+pub mod push_git;
+
+use crate::args::hooks::{head_commit, run_hook, Commits, Hook};
+use crate::lib;
+use lib::{expand_home, is_offline, match_user_input_with_existing_templates, DmanError};
+
+/// Commit local changes of template and push them to remote
+///
+/// Push is cancelled if pre_push hook fails
+/// In offline mode changes are only committed, push hooks don't run
+pub fn push(
+    name: Option<String>,
+    path: Option<String>,
+    git_path: Option<String>,
+    message: Option<String>,
+) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(name, path, git_path)?;
+    let path = expand_home(&template.path);
+
+    if is_offline() {
+        push_git::commit(&path, message)?;
+        println!(
+            "Offline, push {0} with `dman push {0}` when online",
+            template.name
+        );
+        return Ok(());
+    }
+
+    let old = head_commit(&template.path);
+    let commits = Commits {
+        old: old.clone(),
+        new: old,
+    };
+    run_hook(&template, Hook::PrePush, &commits, false)?;
+
+    println!("Pushing changes for: {}", template.name);
+    // Pass path from matched template to function, that'll push changes to GitHub
+    push_git::run(
+        path.to_string_lossy().to_string(),
+        template.remote_name(),
+        message,
+    )?;
+
+    let commits = Commits {
+        new: head_commit(&template.path),
+        ..commits
+    };
+    run_hook(&template, Hook::PostPush, &commits, false)?;
+
+    Ok(())
+}
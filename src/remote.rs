@@ -0,0 +1,167 @@
+use super::{
+    config::Config, credentials::remote_callbacks, get_data_folder, is_offline, DmanError,
+    PROMPT_LOCK,
+};
+use git2::{Direction, Remote};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        TryLockError,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Remotes, that were checked, stored in ~/.local/share/dotfile-manager/remotes.toml
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Cache {
+    /// Reachable remotes and when they were checked
+    #[serde(default)]
+    reachable: BTreeMap<String, u64>,
+    /// Remotes, whose check was deferred in offline mode
+    #[serde(default)]
+    pending: BTreeSet<String>,
+}
+
+impl Cache {
+    fn path() -> Result<PathBuf, DmanError> {
+        Ok(get_data_folder()?.join("remotes.toml"))
+    }
+
+    /// Read cache, broken or missing cache is empty
+    fn read() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self) -> Result<(), DmanError> {
+        let contents = toml::to_string(self)
+            .map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize remotes: {e}")))?;
+        fs::write(Self::path()?, contents)?;
+
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Connect to remote without repository on disk, like `git ls-remote`
+fn connect(url: String, timeout: Duration) -> Result<(), DmanError> {
+    let (sender, receiver) = mpsc::channel();
+    let remote = url.clone();
+
+    // libgit2 has no connect timeout, so connection is waited for in other thread,
+    // thread of remote, that doesn't respond, is left behind
+    thread::spawn(move || {
+        let result = Remote::create_detached(remote.as_str()).and_then(|mut remote| {
+            remote
+                .connect_auth(Direction::Fetch, Some(remote_callbacks()), None)
+                .map(|_| ())
+        });
+        // Receiver is gone after timeout
+        let _ = sender.send(result);
+    });
+
+    // Time user spends answering credential prompt isn't counted
+    let tick = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    loop {
+        match receiver.recv_timeout(tick) {
+            Ok(result) => return Ok(result?),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(DmanError::InvalidInput(format!(
+                    "Checking remote {url} failed, see message above"
+                )))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if !matches!(PROMPT_LOCK.try_lock(), Err(TryLockError::WouldBlock)) {
+                    waited += tick;
+                }
+                if !timeout.is_zero() && waited >= timeout {
+                    return Err(DmanError::InvalidInput(format!(
+                        "Remote {url} didn't respond in {}s",
+                        timeout.as_secs()
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Check if remote exists and can be connected to
+///
+/// Reachable remotes are cached, so remote isn't checked again until
+/// `remote.cache_secs` from config.toml passes
+/// In offline mode remote isn't contacted, it's recorded as pending and checked
+/// by `dman doctor` later
+///
+/// # Errors
+///
+/// * If remote can't be connected to
+/// * If remote doesn't respond in `remote.timeout_secs` from config.toml, 0 waits forever
+/// * If config.toml can't be read
+///
+/// # Example
+///
+/// ```no_run
+/// use dotfile_manager::check_if_remote_exists;
+/// # fn main() -> Result<(), dotfile_manager::DmanError> {
+///
+/// check_if_remote_exists(String::from("https://github.com/Adamekka/dotfile-manager.git"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn check_if_remote_exists(remote: String) -> Result<(), DmanError> {
+    let config = Config::load()?.remote;
+    let mut cache = Cache::read();
+
+    if is_offline() {
+        if cache.pending.insert(remote.clone()) {
+            cache.write()?;
+        }
+        println!("Offline, remote {remote} will be checked by `dman doctor`");
+        return Ok(());
+    }
+
+    let now = now();
+    if cache
+        .reachable
+        .get(&remote)
+        .is_some_and(|checked| now.saturating_sub(*checked) < config.cache_secs)
+    {
+        return Ok(());
+    }
+
+    let result = connect(remote.clone(), Duration::from_secs(config.timeout_secs));
+
+    // Remote was checked, so it isn't pending anymore, even if it's unreachable
+    let mut changed = cache.pending.remove(&remote);
+    if result.is_ok() && config.cache_secs > 0 {
+        cache
+            .reachable
+            .retain(|_, checked| now.saturating_sub(*checked) < config.cache_secs);
+        cache.reachable.insert(remote, now);
+        changed = true;
+    }
+    if changed {
+        cache.write()?;
+    }
+
+    result
+}
+
+/// Get remotes, whose check was deferred in offline mode
+pub fn pending_remotes() -> Vec<String> {
+    Cache::read().pending.into_iter().collect()
+}
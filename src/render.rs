@@ -0,0 +1,118 @@
+use crate::lib;
+use lib::{machine::Machine, DmanError};
+use std::{
+    collections::BTreeMap,
+    env, io,
+    path::{Path, PathBuf},
+};
+use tera::{Context, Tera};
+use walkdir::WalkDir;
+
+/// Extension of files, that are rendered before they're deployed
+const TEMPLATE_EXTENSION: &str = "tmpl";
+
+/// Check if file has to be rendered before it's deployed
+pub fn is_templated(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == TEMPLATE_EXTENSION)
+}
+
+/// Remove .tmpl extension from target of templated file
+pub fn rendered_target(target: &Path) -> PathBuf {
+    if is_templated(target) {
+        target.with_extension("")
+    } else {
+        target.to_path_buf()
+    }
+}
+
+fn username() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// Variables available in templated files
+///
+/// * `hostname`, `os`, `arch`, `username`
+/// * `tags` - tags of machine from machine.toml
+/// * `vars` - variables from machine.toml
+/// * `env` - environment variables
+fn context(machine: &Machine) -> Context {
+    let env: BTreeMap<String, String> = env::vars().collect();
+
+    let mut context = Context::new();
+    context.insert("hostname", &machine.hostname);
+    context.insert("os", env::consts::OS);
+    context.insert("arch", env::consts::ARCH);
+    context.insert("username", &username());
+    context.insert("tags", &machine.tags);
+    context.insert("vars", &machine.variables);
+    context.insert("env", &env);
+
+    context
+}
+
+/// Renders templated files of one repository
+pub struct Renderer {
+    tera: Tera,
+    context: Context,
+    root: PathBuf,
+}
+
+impl Renderer {
+    /// Load every templated file of repository, so they can include each other
+    /// by path relative to repository, e.g. `{% include "git/work.tmpl" %}`
+    ///
+    /// # Errors
+    ///
+    /// * If repository can't be read
+    /// * If any templated file has invalid syntax
+    pub fn new(root: &Path, machine: &Machine) -> Result<Self, DmanError> {
+        let mut files = Vec::new();
+        let walker = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git");
+
+        for entry in walker {
+            let entry = entry.map_err(io::Error::from)?;
+            if entry.file_type().is_file() && is_templated(entry.path()) {
+                let name = entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap_or(entry.path())
+                    .to_string_lossy()
+                    .to_string();
+                files.push((entry.path().to_path_buf(), Some(name)));
+            }
+        }
+
+        let mut tera = Tera::default();
+        // Deployed files aren't HTML
+        tera.autoescape_on(Vec::new());
+        tera.add_template_files(files)
+            .map_err(|source| DmanError::Render {
+                file: root.to_string_lossy().to_string(),
+                source,
+            })?;
+
+        Ok(Self {
+            tera,
+            context: context(machine),
+            root: root.to_path_buf(),
+        })
+    }
+
+    /// Render templated file of repository
+    pub fn render(&self, source: &Path) -> Result<String, DmanError> {
+        let name = source
+            .strip_prefix(&self.root)
+            .unwrap_or(source)
+            .to_string_lossy()
+            .to_string();
+
+        self.tera
+            .render(&name, &self.context)
+            .map_err(|source| DmanError::Render { file: name, source })
+    }
+}
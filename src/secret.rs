@@ -0,0 +1,445 @@
+use crate::args::deploy::state::{hash_bytes, hash_file};
+use crate::lib;
+use age::{
+    secrecy::ExposeSecret,
+    x25519::{Identity, Recipient},
+};
+use git2::Repository;
+use lib::{
+    expand_home, get_data_folder, get_templates_to_vec, match_user_input_with_existing_templates,
+    save_template, DmanError, Template,
+};
+use mytools::warn;
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+/// Extension of encrypted secrets, that are committed instead of plain files
+pub const SECRET_EXTENSION: &str = "age";
+
+/// Key used to encrypt and decrypt secrets of every template
+fn key_path() -> Result<PathBuf, DmanError> {
+    Ok(get_data_folder()?.join("key.txt"))
+}
+
+/// Write file, that only owner can read when it's created
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), DmanError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)?.write_all(contents)?;
+
+    Ok(())
+}
+
+/// Write key in format of age identity files, so it works with `age -d -i` too
+fn write_key(path: &Path, key: &Identity) -> Result<(), DmanError> {
+    let contents = format!(
+        "# public key: {}\n{}\n",
+        key.to_public(),
+        key.to_string().expose_secret()
+    );
+
+    write_private(path, contents.as_bytes())
+}
+
+fn read_key(path: &Path) -> Result<Identity, DmanError> {
+    let contents = fs::read_to_string(path)?;
+    let line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .unwrap_or_default();
+
+    Identity::from_str(line)
+        .map_err(|e| DmanError::Secret(format!("Couldn't read key {path:?}: {e}")))
+}
+
+/// Load key of this machine
+///
+/// # Errors
+///
+/// * If key doesn't exist, it has to be copied from machine, that encrypted secrets
+/// * If key can't be read
+fn load_key() -> Result<Identity, DmanError> {
+    let path = key_path()?;
+
+    if !path.exists() {
+        return Err(DmanError::Secret(format!(
+            "Key {path:?} doesn't exist, copy it from machine, that encrypted the secrets"
+        )));
+    }
+
+    read_key(&path)
+}
+
+/// Load key of this machine, new key is created if there's none
+fn load_or_create_key() -> Result<Identity, DmanError> {
+    let path = key_path()?;
+
+    if path.exists() {
+        return read_key(&path);
+    }
+
+    let key = Identity::generate();
+    write_key(&path, &key)?;
+    println!("Created key {path:?}, copy it to your other machines to decrypt secrets there");
+
+    Ok(key)
+}
+
+fn encrypt(recipient: &Recipient, plain: &[u8], file: &Path) -> Result<Vec<u8>, DmanError> {
+    age::encrypt(recipient, plain)
+        .map_err(|e| DmanError::Secret(format!("Couldn't encrypt {file:?}: {e}")))
+}
+
+fn decrypt(key: &Identity, encrypted: &Path) -> Result<Vec<u8>, DmanError> {
+    age::decrypt(key, &fs::read(encrypted)?)
+        .map_err(|e| DmanError::Secret(format!("Couldn't decrypt {encrypted:?}: {e}")))
+}
+
+/// Path of encrypted secret, e.g. netrc.age
+pub fn encrypted_path(plain: &Path) -> PathBuf {
+    let mut path = plain.as_os_str().to_owned();
+    path.push(".");
+    path.push(SECRET_EXTENSION);
+
+    PathBuf::from(path)
+}
+
+/// Hashes of secrets as they were decrypted, stored in .git/dman-secrets.toml of repository
+///
+/// Plain file, that doesn't match its hash, was edited and isn't overwritten
+fn hashes_path(repo: &Repository) -> PathBuf {
+    repo.path().join("dman-secrets.toml")
+}
+
+fn load_hashes(repo: &Repository) -> Result<BTreeMap<String, String>, DmanError> {
+    let path = hashes_path(repo);
+
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    toml::from_str(&fs::read_to_string(&path)?).map_err(|source| DmanError::TomlParse {
+        file: path.to_string_lossy().to_string(),
+        source,
+    })
+}
+
+fn save_hashes(repo: &Repository, hashes: &BTreeMap<String, String>) -> Result<(), DmanError> {
+    let contents = toml::to_string(hashes)
+        .map_err(|e| DmanError::InvalidInput(format!("Couldn't serialize hashes: {e}")))?;
+
+    fs::write(hashes_path(repo), contents)?;
+
+    Ok(())
+}
+
+/// Add plain secret to .git/info/exclude and remove it from index,
+/// so it can't be committed by `dman push`
+fn exclude_from_git(repo: &Repository, secret: &str) -> Result<(), DmanError> {
+    let exclude_path = repo.path().join("info/exclude");
+    let pattern = format!("/{secret}");
+    let exclude = fs::read_to_string(&exclude_path).unwrap_or_default();
+
+    if !exclude.lines().any(|line| line == pattern) {
+        if let Some(parent) = exclude_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&exclude_path)?;
+        if !exclude.is_empty() && !exclude.ends_with('\n') {
+            writeln!(file)?;
+        }
+        writeln!(file, "{pattern}")?;
+    }
+
+    let mut index = repo.index()?;
+    if index.get_path(Path::new(secret), 0).is_some() {
+        index.remove_path(Path::new(secret))?;
+        index.write()?;
+        warn!("{secret} was committed before, it's removed from index, but stays in Git history");
+    }
+
+    Ok(())
+}
+
+/// Get path of secret relative to repository
+///
+/// Relative paths are relative to repository, absolute ones have to be inside it
+fn relative_secret_path(root: &Path, file: &str) -> Result<String, DmanError> {
+    let path = expand_home(file);
+    let relative = if path.is_absolute() {
+        path.strip_prefix(root)
+            .map_err(|_| {
+                DmanError::InvalidInput(format!("{path:?} isn't inside repository {root:?}"))
+            })?
+            .to_path_buf()
+    } else {
+        path
+    };
+
+    if relative
+        .extension()
+        .is_some_and(|extension| extension == SECRET_EXTENSION)
+    {
+        return Err(DmanError::InvalidInput(format!(
+            "{relative:?} is already encrypted, use path of the plain file"
+        )));
+    }
+
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Decrypt secrets of template next to their encrypted files
+///
+/// Plain files edited since they were decrypted are kept
+///
+/// # Errors
+///
+/// * If template has secrets, but this machine has no key
+/// * If any secret couldn't be decrypted, the rest is decrypted anyway
+pub fn decrypt_secrets(template: &Template) -> Result<(), DmanError> {
+    if template.secrets.is_empty() {
+        return Ok(());
+    }
+
+    let key = load_key()?;
+    let root = expand_home(&template.path);
+    let repo = Repository::open(&root)?;
+    let mut hashes = load_hashes(&repo)?;
+    let mut failed = 0;
+
+    for secret in &template.secrets {
+        let plain = root.join(secret);
+        let encrypted = encrypted_path(&plain);
+
+        let contents = match decrypt(&key, &encrypted) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("{e}");
+                failed += 1;
+                continue;
+            }
+        };
+        let hash = hash_bytes(&contents);
+
+        if plain.is_file() {
+            let current = hash_file(&plain)?;
+
+            if current == hash {
+                hashes.insert(secret.clone(), hash);
+                continue;
+            }
+
+            if hashes.get(secret) != Some(&current) {
+                warn!(
+                    "{plain:?} was edited since it was decrypted, keeping it, \
+                     encrypt it with `dman secret add {} {secret}`",
+                    template.name
+                );
+                continue;
+            }
+        }
+
+        // Fresh clone doesn't exclude plain secrets yet
+        exclude_from_git(&repo, secret)?;
+        write_private(&plain, &contents)?;
+        hashes.insert(secret.clone(), hash);
+        println!("Decrypted {encrypted:?}");
+    }
+
+    save_hashes(&repo, &hashes)?;
+
+    if failed > 0 {
+        return Err(DmanError::InvalidInput(format!(
+            "{failed} secret(s) of {} couldn't be decrypted",
+            template.name
+        )));
+    }
+
+    Ok(())
+}
+
+/// Encrypt files of template and mark them as secrets
+///
+/// Encrypted files are committed by `dman push`, plain files are excluded from Git
+/// Key is created if this machine has none
+///
+/// # Errors
+///
+/// * If template doesn't exist
+/// * If file isn't inside repository or doesn't exist
+/// * If key, file or template file can't be read or written
+pub fn add_secrets(name: String, files: Vec<String>) -> Result<(), DmanError> {
+    let mut template = match_user_input_with_existing_templates(Some(name), None, None)?;
+    let root = expand_home(&template.path);
+    let repo = Repository::open(&root)?;
+    let mut hashes = load_hashes(&repo)?;
+    let key = load_or_create_key()?;
+
+    for file in files {
+        let secret = relative_secret_path(&root, &file)?;
+        let plain = root.join(&secret);
+
+        if !plain.is_file() {
+            return Err(DmanError::NotFound(format!("File {plain:?}")));
+        }
+
+        let contents = fs::read(&plain)?;
+        let encrypted = encrypted_path(&plain);
+        fs::write(&encrypted, encrypt(&key.to_public(), &contents, &plain)?)?;
+        exclude_from_git(&repo, &secret)?;
+        hashes.insert(secret.clone(), hash_bytes(&contents));
+        println!("Encrypted {plain:?} to {encrypted:?}");
+
+        if !template.secrets.contains(&secret) {
+            template.secrets.push(secret);
+        }
+    }
+
+    save_hashes(&repo, &hashes)?;
+    save_template(&template)?;
+    println!("Commit encrypted files with `dman push {}`", template.name);
+
+    Ok(())
+}
+
+/// Open file in editor set by user, vi if there's none, and read it when editor exits
+fn edit_file(path: &Path) -> Result<Vec<u8>, DmanError> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else {
+        return Err(DmanError::InvalidInput("Editor is empty".to_string()));
+    };
+
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if !status.success() {
+        return Err(DmanError::InvalidInput(format!(
+            "Editor exited with {status}, secret wasn't changed"
+        )));
+    }
+
+    Ok(fs::read(path)?)
+}
+
+/// Decrypt secret into temporary file, open it in editor and encrypt it again
+///
+/// Plain file in repository is updated too
+///
+/// # Errors
+///
+/// * If template doesn't exist or file isn't its secret
+/// * If secret can't be decrypted or encrypted
+/// * If editor fails
+pub fn edit_secret(name: String, file: String) -> Result<(), DmanError> {
+    let template = match_user_input_with_existing_templates(Some(name), None, None)?;
+    let root = expand_home(&template.path);
+    let secret = relative_secret_path(&root, &file)?;
+
+    if !template.secrets.contains(&secret) {
+        return Err(DmanError::NotFound(format!(
+            "Secret {secret} of {}",
+            template.name
+        )));
+    }
+
+    let key = load_key()?;
+    let plain = root.join(&secret);
+    let encrypted = encrypted_path(&plain);
+    let contents = decrypt(&key, &encrypted)?;
+
+    // Keep file name, so editor can highlight it
+    let edit_folder = get_data_folder()?.join("edit");
+    fs::create_dir_all(&edit_folder)?;
+    let edit_path = edit_folder.join(plain.file_name().unwrap_or_default());
+    write_private(&edit_path, &contents)?;
+
+    let edited = edit_file(&edit_path);
+    // Plain secret shouldn't be left around
+    fs::remove_file(&edit_path)?;
+    let edited = edited?;
+
+    if edited == contents {
+        println!("{secret} wasn't changed");
+        return Ok(());
+    }
+
+    fs::write(&encrypted, encrypt(&key.to_public(), &edited, &plain)?)?;
+    write_private(&plain, &edited)?;
+
+    let repo = Repository::open(&root)?;
+    let mut hashes = load_hashes(&repo)?;
+    hashes.insert(secret.clone(), hash_bytes(&edited));
+    save_hashes(&repo, &hashes)?;
+
+    println!(
+        "Encrypted {encrypted:?}, commit it with `dman push {}`",
+        template.name
+    );
+
+    Ok(())
+}
+
+/// Create new key and encrypt secrets of every template with it
+///
+/// Old key is kept as key.old.txt
+/// Every secret is decrypted before anything is written, so nothing changes if one fails
+///
+/// # Errors
+///
+/// * If any secret can't be decrypted with current key
+/// * If key or secrets can't be written
+pub fn rekey_secrets() -> Result<(), DmanError> {
+    let old_key = load_key()?;
+    let new_key = Identity::generate();
+    let mut secrets: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+
+    for template in get_templates_to_vec()? {
+        let root = expand_home(&template.path);
+
+        for secret in &template.secrets {
+            let encrypted = encrypted_path(&root.join(secret));
+            let contents = decrypt(&old_key, &encrypted)?;
+            secrets.push((encrypted, contents));
+        }
+    }
+
+    let key_path = key_path()?;
+    let new_key_path = key_path.with_extension("new.txt");
+    write_key(&new_key_path, &new_key)?;
+
+    for (encrypted, contents) in &secrets {
+        fs::write(
+            encrypted,
+            encrypt(&new_key.to_public(), contents, encrypted)?,
+        )?;
+        println!("Encrypted {encrypted:?} with new key");
+    }
+
+    fs::rename(&key_path, key_path.with_extension("old.txt"))?;
+    fs::rename(&new_key_path, &key_path)?;
+
+    println!(
+        "{} secret(s) encrypted with new key {}",
+        secrets.len(),
+        new_key.to_public()
+    );
+    println!("Push templates with secrets and copy {key_path:?} to your other machines");
+
+    Ok(())
+}
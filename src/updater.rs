@@ -1,10 +1,11 @@
 use crate::args::{create::clone_git, pull::pull_git};
+use crate::lib::{credentials::remote_callbacks, is_offline, DmanError, DEFAULT_REMOTE};
 use git2::Repository;
-use mytools::{env::get_home_folder, pretty_panic, question_yes_no};
+use mytools::{env::get_home_folder, question_yes_no};
 use question::Question;
 use std::{path::Path, process::Command};
 
-fn clone_dman_repo() -> String {
+fn clone_dman_repo() -> Result<String, DmanError> {
     let binding = &get_home_folder();
     let home = Path::new(binding);
     let dman_repo_path = home.join(".local/share/dotfile-manager/src");
@@ -13,39 +14,31 @@ fn clone_dman_repo() -> String {
     if !dman_repo_path.exists() {
         println!("Dotfile-manager repository not found.");
         println!("Cloning dotfile-manager repository...");
-        let result = clone_git::run(dman_repo_remote, &dman_repo_path);
-
-        match result {
-            Ok(_) => println!("Dotfile-manager repository cloned successfully."),
-            Err(e) => {
-                pretty_panic!("Failed to clone dotfile-manager repository: {e}");
-            }
-        }
+        clone_git::run(dman_repo_remote, &dman_repo_path)?;
+        println!("Dotfile-manager repository cloned successfully.");
     }
 
-    dman_repo_path.to_str().unwrap().to_string()
+    Ok(dman_repo_path.to_string_lossy().to_string())
 }
 
-pub fn check_updates() {
+pub fn check_updates() -> Result<(), DmanError> {
+    if is_offline() {
+        return Err(DmanError::Offline("Checking for updates".to_string()));
+    }
+
     println!("Checking for updates...");
     let installed_git_commit_hash = env!("DMAN_GIT_COMMIT_HASH");
-    let dman_repo_path = clone_dman_repo();
+    let dman_repo_path = clone_dman_repo()?;
 
     // Get remote git commit hash
-    let repo =
-        Repository::open(&dman_repo_path).expect("Failed to open dotfile-manager repository");
-    let mut remote = repo
-        .find_remote("origin")
-        .expect("Failed to find remote for dotfile-manager repository");
-    let connection = remote
-        .connect_auth(git2::Direction::Fetch, None, None)
-        .unwrap();
+    let repo = Repository::open(&dman_repo_path)?;
+    let mut remote = repo.find_remote(DEFAULT_REMOTE)?;
+    let connection = remote.connect_auth(git2::Direction::Fetch, Some(remote_callbacks()), None)?;
 
     let remote_git_commit_hash = connection
-        .list()
-        .unwrap()
+        .list()?
         .first()
-        .unwrap()
+        .ok_or_else(|| DmanError::NotFound("Remote dotfile-manager commit".to_string()))?
         .oid()
         .to_string()
         .chars()
@@ -62,8 +55,10 @@ pub fn check_updates() {
         println!("Dotfile-manager is not up to date.");
         question_yes_no!("Do you want to update dotfile-manager?");
         println!("Updating dotfile-manager...");
-        update(dman_repo_path);
+        update(dman_repo_path)?;
     }
+
+    Ok(())
 }
 
 enum InstallFeatures {
@@ -71,16 +66,14 @@ enum InstallFeatures {
     CliWithGui,
 }
 
-fn update(dman_repo_path: String) {
+fn update(dman_repo_path: String) -> Result<(), DmanError> {
     let remote_branch: Vec<String> = vec!["main".to_string()];
-    let result = pull_git::run(dman_repo_path.clone(), remote_branch);
-
-    match result {
-        Ok(_) => {}
-        Err(e) => {
-            pretty_panic!("Failed to update dotfile-manager: {e}");
-        }
-    }
+    pull_git::run(
+        dman_repo_path.clone(),
+        DEFAULT_REMOTE,
+        remote_branch,
+        pull_git::PullOptions::default(),
+    )?;
 
     // Install new version
     println!("Installing new version...");
@@ -88,13 +81,12 @@ fn update(dman_repo_path: String) {
         .yes_no()
         .show_defaults()
         .until_acceptable()
-        .ask()
-        .unwrap();
+        .ask();
 
     let install_features = match answer {
-        question::Answer::YES => InstallFeatures::CliWithGui,
-        question::Answer::NO => InstallFeatures::Cli,
-        question::Answer::RESPONSE(_) => unreachable!(),
+        Some(question::Answer::YES) => InstallFeatures::CliWithGui,
+        Some(question::Answer::NO) => InstallFeatures::Cli,
+        _ => unreachable!(),
     };
 
     Command::new("make")
@@ -103,8 +95,8 @@ fn update(dman_repo_path: String) {
             InstallFeatures::Cli => "install",
             InstallFeatures::CliWithGui => "install-gui",
         })
-        .spawn()
-        .expect("Failed to install dotfile-manager")
-        .wait()
-        .expect("Failed to install dotfile-manager");
+        .spawn()?
+        .wait()?;
+
+    Ok(())
 }